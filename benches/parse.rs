@@ -0,0 +1,66 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// 100,000 要素の数値の Array
+fn large_array() -> String {
+    let elements = (0..100_000).map(|i| i.to_string()).collect::<Vec<_>>();
+    format!("[{}]", elements.join(","))
+}
+
+fn bench_large_array(c: &mut Criterion) {
+    let json = large_array();
+    c.bench_function("parse 100k element array", |b| {
+        b.iter(|| json_parser::parse(black_box(&json)).unwrap())
+    });
+}
+
+/// 1,000,000 要素の数値だけの Array
+fn bench_flat_numbers(c: &mut Criterion) {
+    let elements = (0..1_000_000)
+        .map(|i| (i as f64 * 0.5).to_string())
+        .collect::<Vec<_>>();
+    let json = format!("[{}]", elements.join(","));
+    c.bench_function("parse 1M number array", |b| {
+        b.iter(|| json_parser::parse(black_box(&json)).unwrap())
+    });
+}
+
+/// エスケープ文字列を多く含む文字列の Array
+fn escaped_strings() -> String {
+    let element = r#""\u3042\u3044\u3046 \"quoted\" \\path\\to \uD83D\uDE04 \t\n""#;
+    format!("[{}]", vec![element; 10_000].join(","))
+}
+
+fn bench_escaped_strings(c: &mut Criterion) {
+    let json = escaped_strings();
+    c.bench_function("tokenize escape-heavy strings", |b| {
+        b.iter(|| {
+            json_parser::Lexer::new(black_box(&json))
+                .tokenize()
+                .unwrap()
+        })
+    });
+}
+
+/// 長い文字列を 16 バイトずつ受け取る
+fn bench_feed_long_string(c: &mut Criterion) {
+    let json = format!("[\"{}\"]", "a".repeat(1_000_000));
+    c.bench_function("feed 1MB string in 16 byte chunks", |b| {
+        b.iter(|| {
+            let mut lexer = json_parser::StreamLexer::new();
+            for chunk in json.as_bytes().chunks(16) {
+                lexer.feed(std::str::from_utf8(chunk).unwrap()).unwrap();
+            }
+            lexer.finish().unwrap();
+            black_box(lexer.drain_tokens())
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_large_array,
+    bench_flat_numbers,
+    bench_escaped_strings,
+    bench_feed_long_string
+);
+criterion_main!(benches);
@@ -33,9 +33,16 @@ fn do_minimum_output(value: &Value, color: bool) {
         Value::Number(v) => {
             print!("{}", v);
         }
+        #[cfg(feature = "decimal")]
+        Value::Decimal(v) => {
+            print!("{}", v);
+        }
         Value::Bool(v) => {
             print!("{}", v);
         }
+        Value::Raw(raw) => {
+            print!("{}", raw.as_str());
+        }
         Value::String(s) => {
             let s = if color { green(s) } else { s.to_string() };
             print!("\"{}\"", s);
@@ -77,9 +84,16 @@ fn do_output(value: &Value, color: bool, indent: usize, special: bool) {
         Value::Number(v) => {
             print!("{}", v);
         }
+        #[cfg(feature = "decimal")]
+        Value::Decimal(v) => {
+            print!("{}", v);
+        }
         Value::Bool(v) => {
             print!("{}", v)
         }
+        Value::Raw(raw) => {
+            print!("{}", raw.as_str())
+        }
         Value::String(s) => {
             let s = if color { green(s) } else { s.to_string() };
             print!("\"{}\"", s);
@@ -148,10 +162,8 @@ fn do_output(value: &Value, color: bool, indent: usize, special: bool) {
 }
 
 fn main() {
-    let (args, options): (Vec<String>, Vec<String>) = env::args()
-        .into_iter()
-        .skip(1)
-        .partition(|str| !str.starts_with('-'));
+    let (args, options): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|str| !str.starts_with('-'));
 
     let mut color_output = false;
     let mut minimize_output = false;
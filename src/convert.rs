@@ -0,0 +1,190 @@
+use crate::Value;
+use std::collections::BTreeMap;
+
+/// Value を Rust の型に変換する。Raw は Parse した Value を変換する。
+/// 型が一致しない場合は元の Value (Raw の場合は Parse した Value) を返す
+impl TryFrom<Value> for f64 {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.into_resolved() {
+            Value::Number(n) => Ok(n),
+            value => Err(value),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.into_resolved() {
+            Value::Bool(b) => Ok(b),
+            value => Err(value),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.into_resolved() {
+            Value::String(s) => Ok(s),
+            value => Err(value),
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<Value> {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.into_resolved() {
+            Value::Array(array) => Ok(array),
+            value => Err(value),
+        }
+    }
+}
+
+impl TryFrom<Value> for BTreeMap<String, Value> {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value.into_resolved() {
+            Value::Object(object) => Ok(object),
+            value => Err(value),
+        }
+    }
+}
+
+impl Value {
+    /// 文字列以外のキーを持つ Map などから Object を作る。キーは to_string で文字列にする。
+    /// Object はキーを文字列として昇順に並べるので、数値のキーは数値の順にはならない (e.g. "10" < "2")
+    pub fn from_keyed_map<K: ToString>(entries: impl IntoIterator<Item = (K, Value)>) -> Value {
+        Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// 整数のキーを持つ Map から Object を作る
+    pub fn from_i64_keyed_map(map: BTreeMap<i64, Value>) -> Value {
+        Self::from_keyed_map(map)
+    }
+
+    /// Array の場合はその要素を、それ以外の場合は自身だけを要素とする Vec を返す。
+    /// 単一の値と Array のどちらも受け付ける API の入力を揃えるために使う
+    pub fn into_array_coerced(self) -> Vec<Value> {
+        match self.into_resolved() {
+            Value::Array(array) => array,
+            value => vec![value],
+        }
+    }
+
+    /// Array の各要素を複製して T に変換した Vec を返す (e.g. [1, 2] を Vec<f64> に)。
+    /// Array でない、もしくは T に変換できない要素を含む場合は None を返す
+    pub fn as_vec_of<T: TryFrom<Value>>(&self) -> Option<Vec<T>> {
+        match self.resolved() {
+            Value::Array(array) => array.iter().map(|v| T::try_from(v.clone()).ok()).collect(),
+            _ => None,
+        }
+    }
+
+    /// Object の各値を複製して T に変換した Map を返す (e.g. {"a": 1} を BTreeMap<String, f64> に)。
+    /// Object でない、もしくは T に変換できない値を含む場合は None を返す
+    pub fn as_map_of<T: TryFrom<Value>>(&self) -> Option<BTreeMap<String, T>> {
+        match self.resolved() {
+            Value::Object(object) => object
+                .iter()
+                .map(|(k, v)| T::try_from(v.clone()).ok().map(|v| (k.clone(), v)))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, Value};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_try_from() {
+        assert_eq!(f64::try_from(Value::Number(1.5)), Ok(1.5));
+        assert_eq!(bool::try_from(Value::Bool(true)), Ok(true));
+        assert_eq!(
+            String::try_from(Value::String("a".to_string())),
+            Ok("a".to_string())
+        );
+        assert_eq!(f64::try_from(Value::Null), Err(Value::Null));
+    }
+
+    #[test]
+    fn test_into_array_coerced() {
+        assert_eq!(
+            Value::Number(1.0).into_array_coerced(),
+            vec![Value::Number(1.0)]
+        );
+        let object = parse(r#"{"a": 1}"#).unwrap();
+        assert_eq!(object.clone().into_array_coerced(), vec![object]);
+        assert_eq!(
+            parse("[1, null]").unwrap().into_array_coerced(),
+            vec![Value::Number(1.0), Value::Null]
+        );
+        assert_eq!(parse("[]").unwrap().into_array_coerced(), vec![]);
+    }
+
+    #[test]
+    fn test_as_vec_of() {
+        let value = parse("[1, 2, 3]").unwrap();
+        assert_eq!(value.as_vec_of::<f64>(), Some(vec![1.0, 2.0, 3.0]));
+        assert_eq!(
+            parse(r#"["a", "b"]"#).unwrap().as_vec_of::<String>(),
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(parse(r#"[1, "x"]"#).unwrap().as_vec_of::<f64>(), None);
+        assert_eq!(parse(r#"{"a": 1}"#).unwrap().as_vec_of::<f64>(), None);
+    }
+
+    #[test]
+    fn test_as_map_of() {
+        let value = parse(r#"{"a": 1, "b": 2}"#).unwrap();
+        assert_eq!(
+            value.as_map_of::<f64>(),
+            Some(BTreeMap::from([
+                ("a".to_string(), 1.0),
+                ("b".to_string(), 2.0)
+            ]))
+        );
+        assert_eq!(
+            parse("{}").unwrap().as_map_of::<f64>(),
+            Some(BTreeMap::new())
+        );
+
+        let value = parse(r#"{"a": 1, "b": "x"}"#).unwrap();
+        assert_eq!(value.as_map_of::<f64>(), None);
+        assert_eq!(parse("[1]").unwrap().as_map_of::<f64>(), None);
+    }
+
+    #[test]
+    fn test_from_keyed_map() {
+        let mut map = BTreeMap::new();
+        map.insert(2, Value::Bool(true));
+        map.insert(10, Value::Null);
+        map.insert(
+            -1,
+            Value::from_i64_keyed_map(BTreeMap::from([(1, Value::Number(1.0))])),
+        );
+        let value = Value::from_i64_keyed_map(map);
+        assert_eq!(
+            value.to_json_string(),
+            r#"{"-1":{"1":1},"10":null,"2":true}"#
+        );
+
+        let value = Value::from_keyed_map([('a', Value::Number(1.0)), ('b', Value::Null)]);
+        assert_eq!(value.to_json_string(), r#"{"a":1,"b":null}"#);
+    }
+}
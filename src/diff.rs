@@ -0,0 +1,237 @@
+use crate::{pointer::escape_token, Value};
+
+/// 2 つの Value の差分の 1 つ。path は変更された場所を指す JSON Pointer
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// 変更後にのみ存在する
+    Added { path: String, value: Value },
+    /// 変更前にのみ存在する
+    Removed { path: String, value: Value },
+    /// 両方に存在するが値が異なる
+    Replaced {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+/// 差分の末端の path の数。追加・削除された Array/Object はその中の末端の数だけ数える。
+/// 空の Array/Object は flatten_dotted と同じく 1 つの末端として数え、Raw は Parse した Value で数える
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffCounts {
+    /// 変更後にのみ存在する末端の数
+    pub added: usize,
+    /// 変更前にのみ存在する末端の数
+    pub removed: usize,
+    /// 両方に存在するが値が異なる path の数
+    pub changed: usize,
+}
+
+/// 差分を再帰的にたどり、見つけた差分を (path, 変更前, 変更後) で report に渡す。
+/// 追加は変更前が、削除は変更後が None になる。Object はキーごとに、Array は添字ごとに比較する
+fn collect_changes<F>(path: &str, old: &Value, new: &Value, report: &mut F)
+where
+    F: FnMut(String, Option<&Value>, Option<&Value>),
+{
+    match (old.resolved(), new.resolved()) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let child = format!("{}/{}", path, escape_token(key));
+                match new.get(key) {
+                    Some(new_value) => collect_changes(&child, old_value, new_value, report),
+                    None => report(child, Some(old_value), None),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    report(
+                        format!("{}/{}", path, escape_token(key)),
+                        None,
+                        Some(new_value),
+                    );
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                let child = format!("{}/{}", path, index);
+                match (old.get(index), new.get(index)) {
+                    (Some(old_value), Some(new_value)) => {
+                        collect_changes(&child, old_value, new_value, report)
+                    }
+                    (old_value, new_value) => report(child, old_value, new_value),
+                }
+            }
+        }
+        (old, new) if old != new => report(path.to_string(), Some(old), Some(new)),
+        _ => {}
+    }
+}
+
+/// 末端の数を複製せずに数える。空の Array/Object は 1 つの末端として数える
+fn count_leaves(value: &Value) -> usize {
+    match value.resolved() {
+        Value::Array(array) if !array.is_empty() => array.iter().map(count_leaves).sum(),
+        Value::Object(object) if !object.is_empty() => object.values().map(count_leaves).sum(),
+        _ => 1,
+    }
+}
+
+impl Value {
+    /// self から other への差分を返す。同じ Value の場合は空の Vec を返す
+    pub fn diff(&self, other: &Value) -> Vec<Change> {
+        let mut changes = vec![];
+        collect_changes("", self, other, &mut |path, old, new| {
+            changes.push(match (old, new) {
+                (Some(old), Some(new)) => Change::Replaced {
+                    path,
+                    old: old.clone(),
+                    new: new.clone(),
+                },
+                (Some(old), None) => Change::Removed {
+                    path,
+                    value: old.clone(),
+                },
+                (None, Some(new)) => Change::Added {
+                    path,
+                    value: new.clone(),
+                },
+                (None, None) => unreachable!(),
+            })
+        });
+        changes
+    }
+
+    /// self から other への差分を、追加・削除・変更された末端の数で返す。
+    /// 差分の Value は複製しない
+    pub fn diff_counts(&self, other: &Value) -> DiffCounts {
+        let mut counts = DiffCounts::default();
+        collect_changes("", self, other, &mut |_, old, new| match (old, new) {
+            (Some(_), Some(_)) => counts.changed += 1,
+            (Some(old), None) => counts.removed += count_leaves(old),
+            (None, Some(new)) => counts.added += count_leaves(new),
+            (None, None) => unreachable!(),
+        });
+        counts
+    }
+
+    /// self から other への差分を、変更された path ごとに -/+ の行で表した文字列を返す。
+    /// e.g. "- /a: 1\n+ /a: 2\n"
+    pub fn diff_pretty(&self, other: &Value) -> String {
+        let mut output = String::new();
+        for change in self.diff(other) {
+            match change {
+                Change::Added { path, value } => {
+                    output += &format!("+ {}: {}\n", path, value.to_json_string());
+                }
+                Change::Removed { path, value } => {
+                    output += &format!("- {}: {}\n", path, value.to_json_string());
+                }
+                Change::Replaced { path, old, new } => {
+                    output += &format!("- {}: {}\n", path, old.to_json_string());
+                    output += &format!("+ {}: {}\n", path, new.to_json_string());
+                }
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, DiffCounts};
+    use crate::{parse, Value};
+
+    #[test]
+    fn test_diff() {
+        let old = parse(r#"{"a": 1, "b": [1, 2], "c": {"d/e": true}, "f": null}"#).unwrap();
+        let new = parse(r#"{"a": 2, "b": [1], "c": {"d/e": true}, "g": "x"}"#).unwrap();
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                Change::Replaced {
+                    path: "/a".to_string(),
+                    old: Value::Number(1.0),
+                    new: Value::Number(2.0),
+                },
+                Change::Removed {
+                    path: "/b/1".to_string(),
+                    value: Value::Number(2.0),
+                },
+                Change::Removed {
+                    path: "/f".to_string(),
+                    value: Value::Null,
+                },
+                Change::Added {
+                    path: "/g".to_string(),
+                    value: Value::String("x".to_string()),
+                },
+            ]
+        );
+        assert_eq!(old.diff(&old), vec![]);
+        // ルートの型が異なる
+        assert_eq!(
+            Value::Null.diff(&Value::Bool(true)),
+            vec![Change::Replaced {
+                path: "".to_string(),
+                old: Value::Null,
+                new: Value::Bool(true),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_pretty() {
+        let old = parse(r#"{"name": "a", "tags": ["x"], "meta": {"v": 1}}"#).unwrap();
+        let new = parse(r#"{"name": "b", "tags": ["x", "y"], "meta": {"v": 1}}"#).unwrap();
+        let pretty = old.diff_pretty(&new);
+        assert_eq!(pretty, "- /name: \"a\"\n+ /name: \"b\"\n+ /tags/1: \"y\"\n");
+        assert!(pretty.contains("/name"));
+        assert!(pretty.contains("/tags/1"));
+        assert!(!pretty.contains("/meta"));
+        assert_eq!(old.diff_pretty(&old), "");
+    }
+
+    #[test]
+    fn test_diff_counts() {
+        let old = parse(
+            r#"{"id": 1, "name": "a", "tags": ["x", "y"], "meta": {"v": 1, "w": 2}, "old": {"p": [1, 2]}}"#,
+        )
+        .unwrap();
+        let new = parse(
+            r#"{"id": 1, "name": "b", "tags": ["x"], "meta": {"v": 3, "w": 4}, "new": {"q": 1, "r": []}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            old.diff_counts(&new),
+            DiffCounts {
+                added: 2,
+                removed: 3,
+                changed: 3,
+            }
+        );
+        assert_eq!(old.diff_counts(&old), DiffCounts::default());
+
+        // 追加された空の Array/Object は 1 つ、Raw は中身の末端の数だけ数える
+        let new = Value::Array(vec![
+            parse("[]").unwrap(),
+            Value::raw(r#"{"a": 1, "b": [2, 3]}"#).unwrap(),
+        ]);
+        assert_eq!(
+            parse("[]").unwrap().diff_counts(&new),
+            DiffCounts {
+                added: 4,
+                removed: 0,
+                changed: 0,
+            }
+        );
+        assert_eq!(
+            Value::Null.diff_counts(&new),
+            DiffCounts {
+                added: 0,
+                removed: 0,
+                changed: 1,
+            }
+        );
+    }
+}
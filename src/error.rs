@@ -0,0 +1,164 @@
+use crate::{lexer::LexerError, parser::ParserError};
+use std::fmt;
+
+/// JSON の処理中に発生したエラー
+#[derive(Debug)]
+pub enum JsonError {
+    /// 字句解析中に発生したエラー
+    Lexer(LexerError),
+    /// 構文解析中に発生したエラー
+    Parser(ParserError),
+    /// Value の操作中に発生したエラー
+    Value(ValueError),
+}
+
+/// Value の操作中に発生したエラー
+#[derive(Debug, Clone)]
+pub struct ValueError {
+    /// エラーメッセージ
+    pub msg: String,
+    /// エラーが発生した位置を表す JSON Pointer (e.g. /tags/1)。分からない場合やトップレベルの場合は空文字列
+    pub path: String,
+}
+
+impl ValueError {
+    pub fn new(msg: &str) -> ValueError {
+        ValueError {
+            msg: msg.to_string(),
+            path: String::new(),
+        }
+    }
+}
+
+impl From<LexerError> for JsonError {
+    fn from(e: LexerError) -> Self {
+        JsonError::Lexer(e)
+    }
+}
+
+impl From<ParserError> for JsonError {
+    fn from(e: ParserError) -> Self {
+        JsonError::Parser(e)
+    }
+}
+
+/// 字句解析のエラーを、位置を保ったまま ParserError にする
+impl From<LexerError> for ParserError {
+    fn from(e: LexerError) -> Self {
+        let mut error = ParserError::new(&e.msg);
+        (error.line, error.column) = (e.line, e.column);
+        error
+    }
+}
+
+impl From<ValueError> for JsonError {
+    fn from(e: ValueError) -> Self {
+        JsonError::Value(e)
+    }
+}
+
+/// msg を表示する。位置が分かる場合は "error at line 3, column 12: ..." の形に、
+/// JSON Pointer の path も分かる場合は "error at /users/2 (line 3, column 12): ..." の形にする
+fn fmt_located(
+    f: &mut fmt::Formatter<'_>,
+    msg: &str,
+    path: &str,
+    line: usize,
+    column: usize,
+) -> fmt::Result {
+    let stripped = msg.strip_prefix("error: ").unwrap_or(msg);
+    match (path.is_empty(), line) {
+        (true, 0) => write!(f, "{}", msg),
+        (true, _) => write!(f, "error at line {}, column {}: {}", line, column, stripped),
+        (false, 0) => write!(f, "error at {}: {}", path, stripped),
+        (false, _) => write!(
+            f,
+            "error at {} (line {}, column {}): {}",
+            path, line, column, stripped
+        ),
+    }
+}
+
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_located(f, &self.msg, "", self.line, self.column)
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_located(f, &self.msg, &self.path, self.line, self.column)
+    }
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_located(f, &self.msg, &self.path, 0, 0)
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Lexer(e) => e.fmt(f),
+            JsonError::Parser(e) => e.fmt(f),
+            JsonError::Value(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for LexerError {}
+
+impl std::error::Error for ParserError {}
+
+impl std::error::Error for ValueError {}
+
+/// Display が中のエラーをそのまま表示するので、source は返さない (エラーの連鎖で同じ内容が二重に表示されないように)
+impl std::error::Error for JsonError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, parse_with_comments, JsonError, Lexer, Parser, ParserError};
+    use std::error::Error;
+
+    #[test]
+    fn test_display() {
+        let boxed = || -> Result<(), Box<dyn Error>> {
+            parse("{\n  \"a\": 1,\n  \"b\" 2\n}")?;
+            Ok(())
+        };
+        assert_eq!(
+            boxed().unwrap_err().to_string(),
+            "error at line 3, column 7: a pair (key(string) and :token) token is expected"
+        );
+
+        // path が分かる場合は path も表示する
+        assert_eq!(
+            parse(r#"{"users":[1,2,{"name" 1}]}"#)
+                .unwrap_err()
+                .to_string(),
+            "error at /users/2 (line 1, column 23): a pair (key(string) and :token) token is expected"
+        );
+        let error = Parser::new(Lexer::new("[[1, 2 3]]").tokenize().unwrap())
+            .parse()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "error at /0: a | or, token is expected Number(3.0)"
+        );
+
+        // 位置が分からない場合は msg をそのまま表示する
+        assert_eq!(
+            ParserError::new("error: something").to_string(),
+            "error: something"
+        );
+
+        let error = parse_with_comments("[1, x]").unwrap_err();
+        assert!(matches!(error, JsonError::Lexer(_)));
+        assert_eq!(
+            error.to_string(),
+            "error at line 1, column 5: an unexpected char x"
+        );
+        assert!(error.source().is_none());
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{pointer::split_pointer, JsonError, Value, ValueError};
+use std::collections::BTreeMap;
+
+/// unflatten で組み立て中の木
+enum Node {
+    Leaf(Value),
+    Branch(BTreeMap<String, Node>),
+}
+
+impl Node {
+    /// path の位置に value を追加する。既に末端がある位置や、末端の下に追加しようとした場合はエラー
+    fn insert(&mut self, path: &[String], value: Value, key: &str) -> Result<(), JsonError> {
+        let conflict = || {
+            JsonError::from(ValueError::new(&format!(
+                "error: a conflicting path {:?}",
+                key
+            )))
+        };
+        match (self, path.split_first()) {
+            (Node::Branch(children), Some((first, rest))) => match children.get_mut(first) {
+                Some(child) => child.insert(rest, value, key),
+                None if rest.is_empty() => {
+                    children.insert(first.clone(), Node::Leaf(value));
+                    Ok(())
+                }
+                None => {
+                    let mut child = Node::Branch(BTreeMap::new());
+                    child.insert(rest, value, key)?;
+                    children.insert(first.clone(), child);
+                    Ok(())
+                }
+            },
+            _ => Err(conflict()),
+        }
+    }
+
+    /// Value に変換する。キーが 0 から連続する添字だけの場合は Array にする
+    fn into_value(self) -> Value {
+        match self {
+            Node::Leaf(value) => value,
+            Node::Branch(children) => {
+                let mut indexes = children
+                    .keys()
+                    .map(|key| match key.parse::<usize>() {
+                        Ok(i) if i.to_string() == *key => Some(i),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .unwrap_or_default();
+                indexes.sort_unstable();
+                let is_array =
+                    !indexes.is_empty() && indexes.iter().enumerate().all(|(i, index)| i == *index);
+
+                if is_array {
+                    let mut children = children
+                        .into_iter()
+                        .map(|(key, node)| (key.parse::<usize>().unwrap_or_default(), node))
+                        .collect::<Vec<_>>();
+                    children.sort_by_key(|(i, _)| *i);
+                    Value::Array(
+                        children
+                            .into_iter()
+                            .map(|(_, node)| node.into_value())
+                            .collect(),
+                    )
+                } else {
+                    Value::Object(
+                        children
+                            .into_iter()
+                            .map(|(key, node)| (key, node.into_value()))
+                            .collect(),
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// a.b\.c のようなドット区切りのキーを分割する (\ の次の文字はエスケープされた文字)
+fn split_dotted(key: &str) -> Vec<String> {
+    let mut segments = vec![String::new()];
+    let mut chars = key.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => segments.last_mut().unwrap().extend(chars.next()),
+            '.' => segments.push(String::new()),
+            c => segments.last_mut().unwrap().push(c),
+        }
+    }
+    segments
+}
+
+impl Value {
+    /// 木をたどり、各末端の Value を a.b.0 のようなドット区切りのキーで持つ Map を返す。
+    /// Array の要素は添字をキーにする。空の Array/Object はそのまま末端として扱う。
+    /// 元のキーに含まれる . と \ は \. と \\ にエスケープするので、キーの区切りと区別できる。
+    /// ルート (空文字列) と区別するため、空のキー 1 つだけのパスは \ に、
+    /// JSON Pointer と区別するため、/ で始まるパスの先頭は \/ にする
+    pub fn flatten_dotted(&self) -> BTreeMap<String, Value> {
+        // path はルートの場合 None
+        fn flatten(value: &Value, path: Option<&str>, result: &mut BTreeMap<String, Value>) {
+            let join = |key: &str| match path {
+                Some(path) => format!("{}.{}", path, key),
+                None => key.to_string(),
+            };
+            match value.resolved() {
+                Value::Array(array) if !array.is_empty() => {
+                    array
+                        .iter()
+                        .enumerate()
+                        .for_each(|(i, v)| flatten(v, Some(&join(&i.to_string())), result));
+                }
+                Value::Object(object) if !object.is_empty() => {
+                    object.iter().for_each(|(key, v)| {
+                        let mut key = key.replace('\\', "\\\\").replace('.', "\\.");
+                        if path.is_none() && key.starts_with('/') {
+                            key.insert(0, '\\');
+                        }
+                        flatten(v, Some(&join(&key)), result)
+                    });
+                }
+                _ => {
+                    let key = match path {
+                        Some("") => "\\",
+                        Some(path) => path,
+                        None => "",
+                    };
+                    result.insert(key.to_string(), value.clone());
+                }
+            }
+        }
+
+        let mut result = BTreeMap::new();
+        flatten(self, None, &mut result);
+        result
+    }
+
+    /// flatten_dotted の逆変換。ドット区切りのキー (a.b.0) を持つ Map から木を組み立てる。
+    /// 0 から連続する添字だけをキーに持つ位置は Array になる。
+    /// 同じ位置が末端と途中の両方として現れる (e.g. a と a.b) 場合はエラーを返す
+    pub fn unflatten(map: &BTreeMap<String, Value>) -> Result<Value, JsonError> {
+        Self::unflatten_with(map, |key| match key {
+            "" => Ok(vec![]),
+            key => Ok(split_dotted(key)),
+        })
+    }
+
+    /// JSON Pointer のキー (/a/b/0) を持つ Map から木を組み立てる。
+    /// 組み立て方は unflatten と同じで、JSON Pointer として不正なキーがある場合はエラーを返す
+    pub fn unflatten_pointers(map: &BTreeMap<String, Value>) -> Result<Value, JsonError> {
+        Self::unflatten_with(map, |key| {
+            split_pointer(key).ok_or_else(|| {
+                ValueError::new(&format!("error: an invalid JSON Pointer {:?}", key)).into()
+            })
+        })
+    }
+
+    /// split でキーを経路に分割して木を組み立てる
+    fn unflatten_with(
+        map: &BTreeMap<String, Value>,
+        split: impl Fn(&str) -> Result<Vec<String>, JsonError>,
+    ) -> Result<Value, JsonError> {
+        let mut root: Option<Node> = None;
+        for (key, value) in map {
+            let path = split(key)?;
+
+            match (&mut root, path.is_empty()) {
+                (None, true) => root = Some(Node::Leaf(value.clone())),
+                (None, false) => {
+                    let mut node = Node::Branch(BTreeMap::new());
+                    node.insert(&path, value.clone(), key)?;
+                    root = Some(node);
+                }
+                (Some(node), false) => node.insert(&path, value.clone(), key)?,
+                (Some(_), true) => {
+                    return Err(
+                        ValueError::new(&format!("error: a conflicting path {:?}", key)).into(),
+                    )
+                }
+            }
+        }
+        Ok(root
+            .map(Node::into_value)
+            .unwrap_or_else(|| Value::Object(BTreeMap::new())))
+    }
+
+    /// Object を a[b][0]=c のようなブラケット記法のキーと値の組の一覧に変換する (URL エンコードはしない)。
+    /// 組はキーの順 (Array は添字の順) に並ぶ。文字列はそのまま、null は空文字列、それ以外は JSON 文字列にする。
+    /// 空の Array/Object は組を作らない。self が Object でない場合はエラーを返す
+    pub fn to_param_pairs(&self) -> Result<Vec<(String, String)>, JsonError> {
+        fn collect(value: &Value, key: String, pairs: &mut Vec<(String, String)>) {
+            match value.resolved() {
+                Value::Array(array) => array
+                    .iter()
+                    .enumerate()
+                    .for_each(|(i, v)| collect(v, format!("{}[{}]", key, i), pairs)),
+                Value::Object(object) => object
+                    .iter()
+                    .for_each(|(k, v)| collect(v, format!("{}[{}]", key, k), pairs)),
+                Value::String(s) => pairs.push((key, s.clone())),
+                Value::Null => pairs.push((key, String::new())),
+                _ => pairs.push((key, value.to_json_string())),
+            }
+        }
+
+        match self.resolved() {
+            Value::Object(object) => {
+                let mut pairs = vec![];
+                object
+                    .iter()
+                    .for_each(|(key, v)| collect(v, key.clone(), &mut pairs));
+                Ok(pairs)
+            }
+            _ => Err(ValueError::new(&format!(
+                "error: query parameters can only be made from an object, not {}",
+                self.type_name()
+            ))
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, JsonError, Value};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_flatten_dotted() {
+        let value =
+            parse(r#"{"a": {"b": [1, {"c": null}]}, "d.e": true, "f": [], "g": {}}"#).unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert("a.b.0".to_string(), Value::Number(1.0));
+        expected.insert("a.b.1.c".to_string(), Value::Null);
+        expected.insert(r"d\.e".to_string(), Value::Bool(true));
+        expected.insert("f".to_string(), Value::Array(vec![]));
+        expected.insert("g".to_string(), Value::Object(BTreeMap::new()));
+        assert_eq!(value.flatten_dotted(), expected);
+
+        let mut expected = BTreeMap::new();
+        expected.insert("".to_string(), Value::Number(1.0));
+        assert_eq!(Value::Number(1.0).flatten_dotted(), expected);
+    }
+
+    #[test]
+    fn test_unflatten() {
+        let value =
+            parse(r#"{"a": {"b": [1, {"c": null}], "x.y": "z"}, "d": [], "e": {}, "f": [[true]]}"#)
+                .unwrap();
+        assert_eq!(Value::unflatten(&value.flatten_dotted()).unwrap(), value);
+
+        // 空のキーや / で始まるキーも元に戻る
+        [
+            r#"{"": 1}"#,
+            r#"{"/x": 1}"#,
+            r#"{"": {"": 1}}"#,
+            r#"{"a": {"": 1, "/b": 2}}"#,
+            r#"{"\\": 1, "\\.": 2}"#,
+        ]
+        .into_iter()
+        .for_each(|json| {
+            let value = parse(json).unwrap();
+            assert_eq!(
+                Value::unflatten(&value.flatten_dotted()).unwrap(),
+                value,
+                "{}",
+                json
+            );
+        });
+        assert_eq!(
+            parse(r#"{"": 1, "/x": 2}"#).unwrap().flatten_dotted(),
+            [
+                ("\\".to_string(), Value::Number(1.0)),
+                ("\\/x".to_string(), Value::Number(2.0))
+            ]
+            .into()
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert("/a/b~1c".to_string(), Value::Number(1.0));
+        map.insert("/a/d/0".to_string(), Value::Bool(true));
+        map.insert("/a/d/1".to_string(), Value::Null);
+        assert_eq!(
+            Value::unflatten_pointers(&map).unwrap(),
+            parse(r#"{"a": {"b/c": 1, "d": [true, null]}}"#).unwrap()
+        );
+        // ドット区切りとしては / を含むキーになる
+        assert_eq!(
+            Value::unflatten(&map).unwrap(),
+            parse(r#"{"/a/b~1c": 1, "/a/d/0": true, "/a/d/1": null}"#).unwrap()
+        );
+        map.insert("a".to_string(), Value::Null);
+        assert!(Value::unflatten_pointers(&map).is_err());
+
+        // 0 から連続していない添字は Object のキーになる
+        let mut map = BTreeMap::new();
+        map.insert("a.1".to_string(), Value::Number(1.0));
+        assert_eq!(
+            Value::unflatten(&map).unwrap(),
+            parse(r#"{"a": {"1": 1}}"#).unwrap()
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), Value::Number(1.0));
+        map.insert("a.b".to_string(), Value::Number(2.0));
+        match Value::unflatten(&map) {
+            Err(JsonError::Value(e)) => assert_eq!(e.msg, r#"error: a conflicting path "a.b""#),
+            result => panic!("a conflict error is expected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_to_param_pairs() {
+        let pairs = |json: &str| parse(json).unwrap().to_param_pairs().unwrap();
+        let owned = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            pairs(r#"{"q": "a b&c", "page": 2, "exact": true, "lang": null}"#),
+            owned(&[
+                ("exact", "true"),
+                ("lang", ""),
+                ("page", "2"),
+                ("q", "a b&c")
+            ])
+        );
+        assert_eq!(
+            pairs(
+                r#"{"user": {"name": "taro", "tags": ["a", "b"]}, "ids": [3, 1], "e": [], "f": {}}"#
+            ),
+            owned(&[
+                ("ids[0]", "3"),
+                ("ids[1]", "1"),
+                ("user[name]", "taro"),
+                ("user[tags][0]", "a"),
+                ("user[tags][1]", "b"),
+            ])
+        );
+        assert!(pairs("{}").is_empty());
+        assert!(matches!(
+            Value::Array(vec![]).to_param_pairs(),
+            Err(JsonError::Value(_))
+        ));
+    }
+}
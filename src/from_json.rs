@@ -0,0 +1,257 @@
+use crate::{pointer::escape_token, JsonError, Value, ValueError};
+use std::collections::BTreeMap;
+
+/// Value から型付きの値に変換する。
+/// serde を使わずに、構造体への変換を手で実装するためのトレイト。
+/// 構造体の実装では Value::field でフィールドごとに変換すると、失敗した場所がエラーに含まれる
+pub trait FromJson: Sized {
+    fn from_json(value: &Value) -> Result<Self, JsonError>;
+}
+
+/// 型が一致しない場合のエラー
+fn type_error(expected: &str, value: &Value) -> JsonError {
+    ValueError::new(&format!(
+        "error: expected {}, found {}",
+        expected,
+        value.type_name()
+    ))
+    .into()
+}
+
+/// エラーの path の先頭に、失敗した場所の JSON Pointer の参照トークンを付け加える (e.g. /b => /a/b)。
+/// ValueError 以外のエラーはメッセージを保った ValueError にする
+fn at_token(error: JsonError, token: &str) -> JsonError {
+    let mut error = match error {
+        JsonError::Lexer(e) => ValueError::new(&e.msg),
+        JsonError::Parser(e) => ValueError {
+            path: e.path,
+            ..ValueError::new(&e.msg)
+        },
+        JsonError::Value(e) => e,
+    };
+    error.path.insert_str(0, &format!("/{}", token));
+    error.into()
+}
+
+impl FromJson for Value {
+    fn from_json(value: &Value) -> Result<Self, JsonError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromJson for f64 {
+    fn from_json(value: &Value) -> Result<Self, JsonError> {
+        match value.resolved() {
+            Value::Number(n) => Ok(*n),
+            _ => Err(type_error("number", value)),
+        }
+    }
+}
+
+impl FromJson for bool {
+    fn from_json(value: &Value) -> Result<Self, JsonError> {
+        match value.resolved() {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(type_error("bool", value)),
+        }
+    }
+}
+
+impl FromJson for String {
+    fn from_json(value: &Value) -> Result<Self, JsonError> {
+        match value.resolved() {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(type_error("string", value)),
+        }
+    }
+}
+
+/// null は None になる
+impl<T: FromJson> FromJson for Option<T> {
+    fn from_json(value: &Value) -> Result<Self, JsonError> {
+        match value.resolved() {
+            Value::Null => Ok(None),
+            _ => T::from_json(value).map(Some),
+        }
+    }
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+    fn from_json(value: &Value) -> Result<Self, JsonError> {
+        match value.resolved() {
+            Value::Array(array) => array
+                .iter()
+                .enumerate()
+                .map(|(i, v)| T::from_json(v).map_err(|e| at_token(e, &i.to_string())))
+                .collect(),
+            _ => Err(type_error("array", value)),
+        }
+    }
+}
+
+impl<T: FromJson> FromJson for BTreeMap<String, T> {
+    fn from_json(value: &Value) -> Result<Self, JsonError> {
+        match value.resolved() {
+            Value::Object(object) => object
+                .iter()
+                .map(|(k, v)| {
+                    T::from_json(v)
+                        .map(|v| (k.clone(), v))
+                        .map_err(|e| at_token(e, &escape_token(k)))
+                })
+                .collect(),
+            _ => Err(type_error("object", value)),
+        }
+    }
+}
+
+impl Value {
+    /// FromJson を実装した型に変換する
+    pub fn decode<T: FromJson>(&self) -> Result<T, JsonError> {
+        T::from_json(self)
+    }
+
+    /// Object の key に対応する値を T に変換する。
+    /// key が存在しない場合は null として変換するので、T が Option の場合は None になる。
+    /// 失敗した場合のエラーメッセージには key が含まれる
+    pub fn field<T: FromJson>(&self, key: &str) -> Result<T, JsonError> {
+        match self.resolved() {
+            Value::Object(object) => match object.get(key) {
+                Some(value) => T::from_json(value).map_err(|e| at_token(e, &escape_token(key))),
+                None => T::from_json(&Value::Null).map_err(|_| {
+                    ValueError::new(&format!("error: a missing field {:?}", key)).into()
+                }),
+            },
+            _ => Err(type_error("object", self)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FromJson;
+    use crate::{parse, JsonError, Value, ValueError};
+
+    #[derive(Debug, PartialEq)]
+    struct Address {
+        city: String,
+    }
+
+    impl FromJson for Address {
+        fn from_json(value: &Value) -> Result<Self, JsonError> {
+            Ok(Address {
+                city: value.field("city")?,
+            })
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        name: String,
+        age: f64,
+        tags: Vec<String>,
+        nickname: Option<String>,
+        address: Address,
+    }
+
+    impl FromJson for User {
+        fn from_json(value: &Value) -> Result<Self, JsonError> {
+            Ok(User {
+                name: value.field("name")?,
+                age: value.field("age")?,
+                tags: value.field("tags")?,
+                nickname: value.field("nickname")?,
+                address: value.field("address")?,
+            })
+        }
+    }
+
+    #[derive(Debug)]
+    struct Tags;
+
+    impl FromJson for Tags {
+        fn from_json(value: &Value) -> Result<Self, JsonError> {
+            match value {
+                Value::Array(array) if !array.is_empty() => Ok(Tags),
+                _ => Err(ValueError::new("error: at least one tag required").into()),
+            }
+        }
+    }
+
+    fn error_msg(result: Result<User, JsonError>) -> String {
+        match result.unwrap_err() {
+            JsonError::Value(e) => e.to_string(),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_from_json() {
+        let value = parse(
+            r#"{"name": "togatoga", "age": 20, "tags": ["a", "b"], "address": {"city": "Tokyo"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            value.decode::<User>().unwrap(),
+            User {
+                name: "togatoga".to_string(),
+                age: 20.0,
+                tags: vec!["a".to_string(), "b".to_string()],
+                nickname: None,
+                address: Address {
+                    city: "Tokyo".to_string(),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_json_error() {
+        let value = parse(r#"{"age": 20, "tags": [], "address": {"city": "x"}}"#).unwrap();
+        assert_eq!(
+            error_msg(value.decode()),
+            r#"error: a missing field "name""#
+        );
+
+        let value =
+            parse(r#"{"name": "a", "age": "20", "tags": [], "address": {"city": "x"}}"#).unwrap();
+        assert_eq!(
+            error_msg(value.decode()),
+            "error at /age: expected number, found string"
+        );
+
+        let value =
+            parse(r#"{"name": "a", "age": 20, "tags": ["a", 1], "address": {"city": "x"}}"#)
+                .unwrap();
+        assert_eq!(
+            error_msg(value.decode()),
+            "error at /tags/1: expected string, found number"
+        );
+
+        let value =
+            parse(r#"{"name": "a", "age": 20, "tags": [], "address": {"city": null}}"#).unwrap();
+        assert_eq!(
+            error_msg(value.decode()),
+            "error at /address/city: expected string, found null"
+        );
+
+        // path はメッセージとは別に持つので、"at" で始まるメッセージもそのまま残る
+        let error = parse(r#"{"tags": []}"#)
+            .unwrap()
+            .field::<Tags>("tags")
+            .unwrap_err();
+        match error {
+            JsonError::Value(e) => {
+                assert_eq!(e.msg, "error: at least one tag required");
+                assert_eq!(e.path, "/tags");
+                assert_eq!(e.to_string(), "error at /tags: at least one tag required");
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        assert_eq!(
+            error_msg(parse("[]").unwrap().decode()),
+            "error: expected object, found array"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use crate::lexer::{Lexer, Token};
+use std::ops::Range;
+
+/// シンタックスハイライト用の Token の分類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+    Key,         // Object のキー
+    StringValue, // 文字列の値
+    Number,      // 数値
+    Bool,        // 真偽値
+    Null,        // Null
+    Punctuation, // { } [ ] , :
+    Whitespace,  // 空白
+}
+
+/// 入力の JSON 文字列をバイト単位の範囲ごとに分類する。
+/// 字句解析に失敗した場合は、失敗した位置までの分類を返す
+pub fn highlight(input: &str) -> Vec<(Range<usize>, TokenClass)> {
+    let mut lexer = Lexer::new(input);
+    let mut tokens = vec![];
+    while let Ok(Some(token)) = lexer.next_spanned_token() {
+        tokens.push(token);
+    }
+
+    let mut result: Vec<(Range<usize>, TokenClass)> = vec![];
+    for (i, (token, range)) in tokens.iter().enumerate() {
+        let class = match token {
+            Token::String(_) => {
+                // 空白を読み飛ばして次が : ならキー
+                let is_key = tokens[i + 1..]
+                    .iter()
+                    .find(|(t, _)| *t != Token::WhiteSpace)
+                    .is_some_and(|(t, _)| *t == Token::Colon);
+                if is_key {
+                    TokenClass::Key
+                } else {
+                    TokenClass::StringValue
+                }
+            }
+            Token::Number(_) => TokenClass::Number,
+            #[cfg(feature = "decimal")]
+            Token::Decimal(_) => TokenClass::Number,
+            Token::Bool(_) => TokenClass::Bool,
+            Token::Null => TokenClass::Null,
+            Token::WhiteSpace => TokenClass::Whitespace,
+            Token::LeftBrace
+            | Token::RightBrace
+            | Token::LeftBracket
+            | Token::RightBracket
+            | Token::Comma
+            | Token::Colon => TokenClass::Punctuation,
+        };
+
+        // 連続する空白は一つの範囲にまとめる
+        match result.last_mut() {
+            Some((last, TokenClass::Whitespace)) if class == TokenClass::Whitespace => {
+                last.end = range.end;
+            }
+            _ => result.push((range.clone(), class)),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{highlight, TokenClass};
+
+    #[test]
+    fn test_highlight() {
+        let json = r#"{"key": [1, true, null, "あ"]}"#;
+        let expected = vec![
+            (0..1, TokenClass::Punctuation),
+            (1..6, TokenClass::Key),
+            (6..7, TokenClass::Punctuation),
+            (7..8, TokenClass::Whitespace),
+            (8..9, TokenClass::Punctuation),
+            (9..10, TokenClass::Number),
+            (10..11, TokenClass::Punctuation),
+            (11..12, TokenClass::Whitespace),
+            (12..16, TokenClass::Bool),
+            (16..17, TokenClass::Punctuation),
+            (17..18, TokenClass::Whitespace),
+            (18..22, TokenClass::Null),
+            (22..23, TokenClass::Punctuation),
+            (23..24, TokenClass::Whitespace),
+            (24..29, TokenClass::StringValue),
+            (29..30, TokenClass::Punctuation),
+            (30..31, TokenClass::Punctuation),
+        ];
+        assert_eq!(highlight(json), expected);
+
+        let json = "{\n  \"a\" : \"b\"\n}";
+        let classes = highlight(json);
+        assert_eq!(classes[1], (1..4, TokenClass::Whitespace));
+        assert_eq!(classes[2], (4..7, TokenClass::Key));
+        assert_eq!(classes[6], (10..13, TokenClass::StringValue));
+
+        // エスケープを含む文字列も入力上の範囲を返す
+        let json = r#"["a\"b\\", 1]"#;
+        assert_eq!(
+            highlight(json),
+            vec![
+                (0..1, TokenClass::Punctuation),
+                (1..9, TokenClass::StringValue),
+                (9..10, TokenClass::Punctuation),
+                (10..11, TokenClass::Whitespace),
+                (11..12, TokenClass::Number),
+                (12..13, TokenClass::Punctuation),
+            ]
+        );
+    }
+}
@@ -1,7 +1,11 @@
+use std::ops::Range;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     String(String), // 文字列
     Number(f64),    // 数値
+    #[cfg(feature = "decimal")]
+    Decimal(rust_decimal::Decimal), // 任意精度の数値
     Bool(bool),     // 真偽値
     Null,           // Null
     WhiteSpace,     // 空白
@@ -13,10 +17,206 @@ pub enum Token {
     Colon,          // :
 }
 
+/// 入力文字列のバイト単位の位置を、1 から始まる (行, 列) に変換する。列は文字単位で数える
+pub(crate) fn line_column(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset.min(input.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (line, before[line_start..].chars().count() + 1)
+}
+
 // JSONの文字列をParseして Token 単位に分割
 pub struct Lexer<'a> {
+    /// 入力文字列 (エラーの位置を求めるために使う)
+    input: &'a str,
     /// 読込中の先頭文字列を指す
     chars: std::iter::Peekable<std::str::Chars<'a>>,
+    /// 読み込み済みのバイト数 (入力文字列に対するバイト単位の位置)
+    offset: usize,
+    /// 字句解析の設定
+    options: LexerOptions,
+    /// 読み込んだコメント (allow_comments の場合のみ)
+    comments: Vec<Comment>,
+    /// 寛容な設定で受け入れた記述の説明と、その位置 (バイト単位)
+    warnings: Vec<(String, usize)>,
+}
+
+/// 入力を少しずつ受け取って Token に分割する Lexer。
+/// 入力全体が揃う前に、完成した Token から順に取り出せる
+#[derive(Debug, Default)]
+pub struct StreamLexer {
+    /// 字句解析の設定
+    options: LexerOptions,
+    /// 受け取った入力のうち、まだ Token にできていない部分
+    buffer: String,
+    /// 読み込み済みで、drain_tokens でまだ取り出されていない Token
+    pending: Vec<Token>,
+    /// 読み込み済みの Token の数
+    fed_tokens: usize,
+    /// 受け取った入力のうち、buffer から取り除いたバイト数
+    fed_offset: usize,
+    /// 読み込んだコメント (allow_comments の場合のみ)
+    comments: Vec<Comment>,
+}
+
+/// コメントの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    Line,  // "// ..."
+    Block, // "/* ... */"
+}
+
+/// 入力中のコメント
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// コメントの種類
+    pub kind: CommentKind,
+    /// コメントの本文 (// や /* */ は含まない)
+    pub text: String,
+    /// コメントの開始位置 (入力文字列に対するバイト単位の位置)
+    pub position: usize,
+}
+
+/// 字句解析の設定
+#[derive(Debug, Clone, Default)]
+pub struct LexerOptions {
+    /// 出力する Token (空白を除く) の最大数。超えた時点で字句解析を中断する
+    pub max_tokens: Option<usize>,
+    /// "// ..." と "/* ... */" のコメントを空白として読み飛ばす
+    pub allow_comments: bool,
+    /// -0 (e.g. "-0", "-0.0e1") を 0 として読み込む。-0 == 0 だが、そのままだとシリアライズで "-0" と出力される。
+    /// Decimal の場合は、加えて末尾の 0 を取り除く (e.g. 1.50 => 1.5)。
+    /// 正規の綴り (最短の表現) を記録するものではない。f64 はもともと値だけを持つので、"1.0" と "1E0" はこの設定によらず同じ 1 になる
+    pub normalize_negative_zero: bool,
+    /// Python の str(dict) などで出力される True/False/None を true/false/null として読み込む
+    pub allow_python_literals: bool,
+    /// 数値の桁区切りの _ (e.g. 1_000_000) を許可する。_ の前後はどちらも数字でなければならない
+    pub allow_number_underscores: bool,
+    /// 引用符の無い単語 (e.g. ok, in_progress) を文字列として読み込む。
+    /// true/false/null (allow_python_literals の場合は True/False/None も) はそのまま読み込む。
+    /// 入力の誤り (e.g. tru) も文字列になってしまうので、非常に寛容な入力を受け付ける場合にだけ使う
+    pub allow_bare_words: bool,
+    /// 数値を f64 ではなく Decimal として読み込む
+    #[cfg(feature = "decimal")]
+    pub decimal_numbers: bool,
+}
+
+impl StreamLexer {
+    /// 入力を受け取る前の StreamLexer を渡す
+    pub fn new() -> StreamLexer {
+        Self::with_options(LexerOptions::default())
+    }
+
+    /// 設定を受け取り、入力を受け取る前の StreamLexer を渡す
+    pub fn with_options(options: LexerOptions) -> StreamLexer {
+        StreamLexer {
+            options,
+            ..StreamLexer::default()
+        }
+    }
+
+    /// 入力の続きを受け取り、完成した Token を読み込む。
+    /// 末尾の途中までしかない Token (e.g. 閉じていない文字列や、続きがあるかもしれない数値) は
+    /// 次の feed まで持ち越す。読み込んだ Token は drain_tokens で取り出す
+    pub fn feed(&mut self, chunk: &str) -> Result<(), LexerError> {
+        let changed = may_change(&self.buffer, chunk);
+        self.buffer.push_str(chunk);
+        // 持ち越している Token の結果が変わらないなら、読み直さない
+        if !changed {
+            return Ok(());
+        }
+        self.lex_buffer(false)
+    }
+
+    /// 入力の終わりを通知し、持ち越していた Token を読み込む
+    pub fn finish(&mut self) -> Result<(), LexerError> {
+        self.lex_buffer(true)
+    }
+
+    /// 読み込んだ Token を取り出す
+    pub fn drain_tokens(&mut self) -> Vec<Token> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// 読み込んだコメントを返す
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// StreamLexer を消費して、読み込んだコメントを返す
+    pub fn into_comments(self) -> Vec<Comment> {
+        self.comments
+    }
+
+    /// buffer から完成した Token を読み込み、読み込んだ部分を buffer から取り除く
+    fn lex_buffer(&mut self, eof: bool) -> Result<(), LexerError> {
+        let mut lexer = Lexer::with_options(&self.buffer, self.options.clone());
+        let mut consumed = 0;
+
+        loop {
+            match lexer.next_token() {
+                Ok(Some(token)) => {
+                    // 末尾に接している Token は続きがあるかもしれない
+                    let incomplete = !matches!(
+                        token,
+                        Token::String(_)
+                            | Token::LeftBrace
+                            | Token::RightBrace
+                            | Token::LeftBracket
+                            | Token::RightBracket
+                            | Token::Comma
+                            | Token::Colon
+                    );
+                    if !eof && incomplete && lexer.offset == self.buffer.len() {
+                        break;
+                    }
+                    if token != Token::WhiteSpace {
+                        lexer.check_token_limit(self.fed_tokens)?;
+                        self.fed_tokens += 1;
+                        self.pending.push(token);
+                    }
+                    consumed = lexer.offset;
+                }
+                // 入力の末尾
+                Ok(None) => break,
+                // 末尾まで読んで失敗した場合は、続きを受け取れば読み込めるかもしれない
+                Err(_) if !eof && lexer.offset == self.buffer.len() => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let fed_offset = self.fed_offset;
+        self.comments.extend(
+            lexer
+                .comments
+                .into_iter()
+                .filter(|comment| comment.position < consumed)
+                .map(|comment| Comment {
+                    position: comment.position + fed_offset,
+                    ..comment
+                }),
+        );
+        self.fed_offset += consumed;
+        self.buffer.drain(..consumed);
+        Ok(())
+    }
+}
+
+/// 持ち越している Token (buffer の先頭から始まる) に chunk を加えると、読み込みの結果が変わりうるか。
+/// 文字列に " や \ や制御文字以外を加えた場合と、数値に数字を加えた場合は、まだ途中のままで変わらない
+fn may_change(buffer: &str, chunk: &str) -> bool {
+    match buffer.chars().next() {
+        None => true,
+        // エスケープの途中 (e.g. "\u30) の場合は、続きの文字も検証する
+        Some('"') => {
+            buffer.bytes().rev().take(6).any(|b| b == b'\\')
+                || chunk
+                    .chars()
+                    .any(|c| c == '"' || c == '\\' || c.is_control())
+        }
+        Some(c) if c == '-' || c.is_ascii_digit() => !chunk.bytes().all(|b| b.is_ascii_digit()),
+        _ => true,
+    }
 }
 
 /// 字句解析中に発生したエラー
@@ -24,21 +224,43 @@ pub struct Lexer<'a> {
 pub struct LexerError {
     /// エラーメッセージ
     pub msg: String,
+    /// エラーになった Token の開始位置の行 (1 から始まる、分からない場合は 0)
+    pub line: usize,
+    /// エラーになった Token の開始位置の列 (1 から始まる、分からない場合は 0)
+    pub column: usize,
 }
 
 impl LexerError {
     fn new(msg: &str) -> LexerError {
         LexerError {
             msg: msg.to_string(),
+            line: 0,
+            column: 0,
         }
     }
+
+    /// エラーになった位置 (入力文字列に対するバイト単位の位置) を行と列にして設定する
+    fn at(mut self, input: &str, offset: usize) -> LexerError {
+        (self.line, self.column) = line_column(input, offset);
+        self
+    }
 }
 
 impl<'a> Lexer<'a> {
     /// 文字列を受け取り Lexer を渡す
-    pub fn new(input: &str) -> Lexer {
+    pub fn new(input: &str) -> Lexer<'_> {
+        Self::with_options(input, LexerOptions::default())
+    }
+
+    /// 文字列と設定を受け取り Lexer を渡す
+    pub fn with_options(input: &str, options: LexerOptions) -> Lexer<'_> {
         Lexer {
+            input,
             chars: input.chars().peekable(),
+            offset: 0,
+            options,
+            comments: vec![],
+            warnings: vec![],
         }
     }
 
@@ -51,6 +273,7 @@ impl<'a> Lexer<'a> {
                 // 空白は今回は捨てるがデバッグ情報として使える(行、列)
                 Token::WhiteSpace => {}
                 _ => {
+                    self.check_token_limit(tokens.len())?;
                     tokens.push(token);
                 }
             }
@@ -59,14 +282,90 @@ impl<'a> Lexer<'a> {
         Ok(tokens)
     }
 
+    /// 文字列を位置付きの Token 単位に分割する (空白は含まない)。
+    /// 位置は元の入力のバイト単位の範囲で、文字列の Token は前後の " を含む
+    pub fn tokenize_with_offsets(&mut self) -> Result<Vec<(Token, Range<usize>)>, LexerError> {
+        let mut tokens = vec![];
+
+        while let Some((token, range)) = self.next_spanned_token()? {
+            if token != Token::WhiteSpace {
+                self.check_token_limit(tokens.len())?;
+                tokens.push((token, range));
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// 巨大な入力は Parser に渡す前に打ち切る
+    fn check_token_limit(&self, count: usize) -> Result<(), LexerError> {
+        match self.options.max_tokens {
+            Some(max) if count >= max => Err(LexerError::new(&format!(
+                "error: the number of tokens exceeds the limit {}",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// 読み込んだコメントを返す
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Lexer を消費して、読み込んだコメントを返す
+    pub fn into_comments(self) -> Vec<Comment> {
+        self.comments
+    }
+
+    /// 寛容な設定で受け入れた記述の説明と、その位置 (バイト単位) を取り出す
+    pub(crate) fn take_warnings(&mut self) -> Vec<(String, usize)> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// 読み込み済みのバイト数 (失敗した場合は失敗した文字の直後の位置)
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// 寛容な設定で受け入れた記述を記録する
+    fn warn(&mut self, msg: String, offset: usize) {
+        self.warnings.push((msg, offset));
+    }
+
+    /// 一文字分だけ読み進め、読み込んだ位置を更新する
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
+
+    /// 文字列を読み込み、マッチしたTokenとその位置 (バイト単位の範囲) を返す
+    pub(crate) fn next_spanned_token(
+        &mut self,
+    ) -> Result<Option<(Token, Range<usize>)>, LexerError> {
+        let start = self.offset;
+        Ok(self.next_token()?.map(|token| (token, start..self.offset)))
+    }
+
     /// 一文字分だけ読み進め、tokenを返す
     fn next_return_token(&mut self, token: Token) -> Option<Token> {
-        self.chars.next();
+        self.next_char();
         Some(token)
     }
 
-    /// 文字列を読み込み、マッチしたTokenを返す
+    /// 文字列を読み込み、マッチしたTokenを返す。
+    /// エラーの場合は、位置が設定されていなければエラーになった Token の開始位置を設定する
     fn next_token(&mut self) -> Result<Option<Token>, LexerError> {
+        let start = self.offset;
+        self.read_token().map_err(|e| match e.line {
+            0 => e.at(self.input, start),
+            _ => e,
+        })
+    }
+
+    /// 文字列を読み込み、マッチしたTokenを返す
+    fn read_token(&mut self) -> Result<Option<Token>, LexerError> {
         // 先頭の文字列を読み込む
         match self.chars.peek() {
             Some(c) => match c {
@@ -86,7 +385,7 @@ impl<'a> Lexer<'a> {
                 // String は開始文字列 '"'
                 // e.g. "togatoga"
                 '"' => {
-                    self.chars.next();
+                    self.next_char();
                     self.parse_string_token()
                 }
 
@@ -94,6 +393,11 @@ impl<'a> Lexer<'a> {
                 // e.g. 1, -1235, +10, .001
                 c if c.is_numeric() || matches!(c, '+' | '-' | '.') => self.parse_number_token(),
 
+                // 引用符の無い単語は英字か _ で始まる
+                c if self.options.allow_bare_words && (c.is_alphabetic() || *c == '_') => {
+                    self.parse_bare_word()
+                }
+
                 // Boolean の true の開始文字は 't'
                 't' => self.parse_bool_token(true),
 
@@ -103,6 +407,14 @@ impl<'a> Lexer<'a> {
                 // Null の開始文字は 'n'
                 'n' => self.parse_null_token(),
 
+                // Python の True/False/None
+                'T' | 'F' | 'N' if self.options.allow_python_literals => {
+                    self.parse_python_literal()
+                }
+
+                // コメントの開始文字は '/'
+                '/' if self.options.allow_comments => self.parse_comment(),
+
                 // 上記のルールにマッチしない文字はエラー
                 _ => Err(LexerError::new(&format!("error: an unexpected char {}", c))),
             },
@@ -110,9 +422,114 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// コメントを読み込み、空白の Token として返す
+    fn parse_comment(&mut self) -> Result<Option<Token>, LexerError> {
+        let position = self.offset;
+        // 先頭の '/' を捨てる
+        self.next_char();
+
+        let mut text = String::new();
+        let kind = match self.next_char() {
+            // 行コメントは改行まで (改行は空白として残す)
+            Some('/') => {
+                while let Some(&c) = self.chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    self.next_char();
+                    text.push(c);
+                }
+                CommentKind::Line
+            }
+            // ブロックコメントは "*/" まで
+            Some('*') => loop {
+                match self.next_char() {
+                    Some('*') if self.chars.peek() == Some(&'/') => {
+                        self.next_char();
+                        break CommentKind::Block;
+                    }
+                    Some(c) => text.push(c),
+                    None => {
+                        return Err(LexerError::new("error: a block comment is not terminated"))
+                    }
+                }
+            },
+            c => {
+                return Err(LexerError::new(&format!(
+                    "error: a comment must start // or /* {:?}",
+                    c
+                )))
+            }
+        };
+
+        self.comments.push(Comment {
+            kind,
+            text,
+            position,
+        });
+        self.warn("accepted comment".to_string(), position);
+        Ok(Some(Token::WhiteSpace))
+    }
+
+    /// Python の True/False/None を parse する
+    fn parse_python_literal(&mut self) -> Result<Option<Token>, LexerError> {
+        let start = self.offset;
+        let mut s = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_alphabetic() || s.len() == 5 {
+                break;
+            }
+            self.next_char();
+            s.push(c);
+        }
+
+        let token = match s.as_str() {
+            "True" => Token::Bool(true),
+            "False" => Token::Bool(false),
+            "None" => Token::Null,
+            _ => {
+                return Err(LexerError::new(&format!(
+                    "error: True, False or None is expected {}",
+                    s
+                )))
+            }
+        };
+        self.warn(format!("accepted Python literal {}", s), start);
+        Ok(Some(token))
+    }
+
+    /// 引用符の無い単語を読み込み、true/false/null 以外は文字列の Token として返す
+    fn parse_bare_word(&mut self) -> Result<Option<Token>, LexerError> {
+        let start = self.offset;
+        let mut s = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            self.next_char();
+            s.push(c);
+        }
+
+        let python = self.options.allow_python_literals;
+        let token = match s.as_str() {
+            "true" => return Ok(Some(Token::Bool(true))),
+            "false" => return Ok(Some(Token::Bool(false))),
+            "null" => return Ok(Some(Token::Null)),
+            "True" if python => Token::Bool(true),
+            "False" if python => Token::Bool(false),
+            "None" if python => Token::Null,
+            _ => {
+                self.warn(format!("accepted bare word {}", s), start);
+                return Ok(Some(Token::String(s)));
+            }
+        };
+        self.warn(format!("accepted Python literal {}", s), start);
+        Ok(Some(token))
+    }
+
     /// nullの文字列をparseする
     fn parse_null_token(&mut self) -> Result<Option<Token>, LexerError> {
-        let s = (0..4).filter_map(|_| self.chars.next()).collect::<String>();
+        let s = (0..4).filter_map(|_| self.next_char()).collect::<String>();
 
         if s == "null" {
             Ok(Some(Token::Null))
@@ -127,7 +544,7 @@ impl<'a> Lexer<'a> {
     /// (true|false)の文字列をparseする
     fn parse_bool_token(&mut self, b: bool) -> Result<Option<Token>, LexerError> {
         if b {
-            let s = (0..4).filter_map(|_| self.chars.next()).collect::<String>();
+            let s = (0..4).filter_map(|_| self.next_char()).collect::<String>();
 
             if s == "true" {
                 Ok(Some(Token::Bool(true)))
@@ -138,7 +555,7 @@ impl<'a> Lexer<'a> {
                 )))
             }
         } else {
-            let s = (0..5).filter_map(|_| self.chars.next()).collect::<String>();
+            let s = (0..5).filter_map(|_| self.next_char()).collect::<String>();
 
             if s == "false" {
                 Ok(Some(Token::Bool(false)))
@@ -153,67 +570,190 @@ impl<'a> Lexer<'a> {
 
     /// 数字として使用可能な文字まで読み込む。読み込んだ文字列が数字(`f64`)としてParseに成功した場合Tokenを返す。
     fn parse_number_token(&mut self) -> Result<Option<Token>, LexerError> {
+        let start = self.offset;
         let mut number_str = String::new();
 
         while let Some(&c) = self.chars.peek() {
             // 数字に使われる可能性がある文字は読み込み、そうではない文字の場合は読み込みを終了する
-            if c.is_numeric() | matches!(c, '+' | '-' | 'e' | 'E' | '.') {
-                self.chars.next();
+            if c.is_numeric()
+                || matches!(c, '+' | '-' | 'e' | 'E' | '.')
+                || (c == '_' && self.options.allow_number_underscores)
+            {
+                self.next_char();
                 number_str.push(c);
             } else {
                 break;
             }
         }
 
+        // 桁区切りの _ は数字の間にある場合だけ許可して取り除く (e.g. "1__0", "_1", "1_.5" は不正)
+        if number_str.contains('_') {
+            let chars: Vec<char> = number_str.chars().collect();
+            let valid = chars.iter().enumerate().all(|(i, c)| {
+                *c != '_'
+                    || (i > 0
+                        && chars[i - 1].is_ascii_digit()
+                        && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+            });
+            if !valid {
+                return Err(LexerError::new(&format!(
+                    "error: a \"_\" in a number must be between digits {:?}",
+                    number_str
+                )));
+            }
+            number_str.retain(|c| c != '_');
+            self.warn("accepted _ in a number".to_string(), start);
+        }
+
+        // .3 や 3. は許可するが、数字の無い . (e.g. "." や "-.e1") は許可しない
+        let mantissa = number_str.split(['e', 'E']).next().unwrap_or_default();
+        if mantissa.contains('.') && !mantissa.chars().any(|c| c.is_ascii_digit()) {
+            return Err(LexerError::new(&format!(
+                "error: a \".\" in a number must have a digit before or after it {:?}",
+                number_str
+            )));
+        }
+        self.check_number_format(mantissa, start);
+
+        #[cfg(feature = "decimal")]
+        if self.options.decimal_numbers {
+            return match Self::parse_decimal(&number_str)? {
+                Some(Token::Decimal(number)) if self.options.normalize_negative_zero => {
+                    Ok(Some(Token::Decimal(number.normalize())))
+                }
+                token => Ok(token),
+            };
+        }
+
         // 読み込んだ文字列がParseできた場合はTokenを返す
         match number_str.parse::<f64>() {
+            // -0 == 0 なので、-0 も 0 にする
+            Ok(number) if self.options.normalize_negative_zero && number == 0.0 => {
+                Ok(Some(Token::Number(0.0)))
+            }
             Ok(number) => Ok(Some(Token::Number(number))),
-            Err(e) => Err(LexerError::new(&format!("error: {}", e.to_string()))),
+            Err(e) => Err(LexerError::new(&format!("error: {}", e))),
+        }
+    }
+
+    /// 数値の仮数部 mantissa のうち、標準の JSON (RFC 8259) では許可されない記述を記録する
+    fn check_number_format(&mut self, mantissa: &str, start: usize) {
+        let digits = mantissa.trim_start_matches(['+', '-']);
+        if mantissa.starts_with('+') {
+            self.warn("accepted leading + in a number".to_string(), start);
+        }
+        if digits.starts_with('.') {
+            self.warn(
+                "accepted number without a digit before .".to_string(),
+                start,
+            );
+        }
+        if digits.ends_with('.') {
+            self.warn("accepted number without a digit after .".to_string(), start);
+        }
+        if digits.starts_with('0') && digits[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            self.warn("accepted leading zeros in a number".to_string(), start);
+        }
+    }
+
+    /// 数値の文字列を丸めずに Decimal として Parse する。指数表記(e.g. 1e3)にも対応する
+    #[cfg(feature = "decimal")]
+    fn parse_decimal(number_str: &str) -> Result<Option<Token>, LexerError> {
+        use rust_decimal::Decimal;
+        use std::str::FromStr;
+
+        let result = if number_str.contains(['e', 'E']) {
+            Decimal::from_scientific(number_str)
+        } else {
+            Decimal::from_str(number_str)
+        };
+        match result {
+            Ok(number) => Ok(Some(Token::Decimal(number))),
+            Err(e) => Err(LexerError::new(&format!("error: {}", e))),
         }
     }
 
     /// 終端文字'\"'まで文字列を読み込む。UTF-16(\u0000~\uFFFF)や特殊なエスケープ文字(e.g. '\t','\n')も考慮する
     fn parse_string_token(&mut self) -> Result<Option<Token>, LexerError> {
-        let mut utf16: Vec<u16> = vec![];
+        // 組になる低サロゲートを待っている高サロゲート
+        let mut high_surrogate: Option<u16> = None;
         let mut result = String::new();
 
-        while let Some(c1) = self.chars.next() {
+        while let Some(c1) = self.next_char() {
             match c1 {
                 // Escapeの開始文字
                 '\\' => {
                     let c2 = self
-                        .chars
-                        .next()
+                        .next_char()
                         .ok_or_else(|| LexerError::new("error: a next char is expected"))?;
                     if matches!(c2, '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't') {
                         // エスケープ文字列の処理
                         // https://www.rfc-editor.org/rfc/rfc8259#section-7
-                        // utf-16のバッファを文字列にpushしておく
-                        Self::push_utf16(&mut result, &mut utf16)?;
-                        result.push('\\');
-                        result.push(c2);
+                        Self::expect_no_high_surrogate(high_surrogate)?;
+                        result.push(match c2 {
+                            'b' => '\u{0008}',
+                            'f' => '\u{000C}',
+                            'n' => '\n',
+                            'r' => '\r',
+                            't' => '\t',
+                            c => c,
+                        });
                     } else if c2 == 'u' {
                         // UTF-16
                         // \u0000 ~ \uFFFF
                         // \uまで読み込んだので残りの0000~XXXXの4文字を読み込む
                         // UTF-16に関してはエスケープ処理を行う
-                        let hexs = (0..4)
-                            .filter_map(|_| {
-                                let c = self.chars.next()?;
-                                if c.is_ascii_hexdigit() {
-                                    Some(c)
-                                } else {
-                                    None
+                        // 16進数の文字は ASCII なので、固定長のバッファに読み込む
+                        let mut hexs = [0u8; 4];
+                        for hex in hexs.iter_mut() {
+                            let offset = self.offset;
+                            match self.next_char() {
+                                Some(c) if c.is_ascii_hexdigit() => *hex = c as u8,
+                                Some(c) => {
+                                    return Err(LexerError::new(&format!(
+                                        "error: an invalid hex digit {:?} in a \\u escape",
+                                        c
+                                    ))
+                                    .at(self.input, offset))
+                                }
+                                None => {
+                                    return Err(LexerError::new(
+                                        "error: a \\u escape must be followed by 4 hex digits",
+                                    )
+                                    .at(self.input, offset))
                                 }
-                            })
-                            .collect::<Vec<_>>();
+                            }
+                        }
+                        let hexs = std::str::from_utf8(&hexs).unwrap_or_default();
 
-                        match u16::from_str_radix(&hexs.iter().collect::<String>(), 16) {
-                            Ok(code_point) => utf16.push(code_point),
+                        match u16::from_str_radix(hexs, 16) {
+                            // 高サロゲートは次の \u の低サロゲートと組み合わせるので取っておく
+                            Ok(code_point @ 0xD800..=0xDBFF) => {
+                                Self::expect_no_high_surrogate(high_surrogate)?;
+                                high_surrogate = Some(code_point);
+                            }
+                            // 低サロゲートは直前の高サロゲートと組み合わせて 1 文字にする
+                            Ok(code_point @ 0xDC00..=0xDFFF) => {
+                                let high = high_surrogate.take().ok_or_else(|| {
+                                    LexerError::new(&format!(
+                                        "error: a low surrogate \\u{:04X} must follow a high surrogate",
+                                        code_point
+                                    ))
+                                })?;
+                                let code_point = 0x10000
+                                    + ((u32::from(high) - 0xD800) << 10)
+                                    + (u32::from(code_point) - 0xDC00);
+                                result.extend(char::from_u32(code_point));
+                            }
+                            // それ以外はそのまま文字として追加する
+                            Ok(code_point) => {
+                                Self::expect_no_high_surrogate(high_surrogate)?;
+                                result.extend(char::from_u32(code_point.into()));
+                            }
                             Err(e) => {
                                 return Err(LexerError::new(&format!(
                                     "error: a unicode character is expected {}",
-                                    e.to_string()
+                                    e
                                 )))
                             }
                         };
@@ -226,36 +766,30 @@ impl<'a> Lexer<'a> {
                 }
                 // 文字列の終端
                 '\"' => {
-                    Self::push_utf16(&mut result, &mut utf16)?;
+                    Self::expect_no_high_surrogate(high_surrogate)?;
                     return Ok(Some(Token::String(result)));
                 }
                 // それ以外の文字列
                 _ => {
-                    Self::push_utf16(&mut result, &mut utf16)?;
+                    Self::expect_no_high_surrogate(high_surrogate)?;
                     result.push(c1);
                 }
             }
         }
 
         // 文字列の終端である '"' が存在しない場合はエラー
-        Ok(None)
+        Err(LexerError::new("error: a string is not terminated"))
     }
 
-    /// utf16のバッファが存在するならば連結しておく
-    fn push_utf16(result: &mut String, utf16: &mut Vec<u16>) -> Result<(), LexerError> {
-        if utf16.is_empty() {
-            return Ok(());
+    /// 低サロゲートを待っている高サロゲートが無いことを確かめる
+    fn expect_no_high_surrogate(high_surrogate: Option<u16>) -> Result<(), LexerError> {
+        match high_surrogate {
+            Some(high) => Err(LexerError::new(&format!(
+                "error: a high surrogate \\u{:04X} must be followed by a low surrogate",
+                high
+            ))),
+            None => Ok(()),
         }
-        match String::from_utf16(utf16) {
-            Ok(utf16_str) => {
-                result.push_str(&utf16_str);
-                utf16.clear();
-            }
-            Err(e) => {
-                return Err(LexerError::new(&format!("error: {}", e.to_string())));
-            }
-        }
-        Ok(())
     }
 }
 
@@ -291,7 +825,7 @@ mod tests {
             ("0.3", Token::Number(0.3)),
             (".3", Token::Number(0.3)),
         ];
-        number_strs.map(|(input, expect)| {
+        number_strs.into_iter().for_each(|(input, expect)| {
             let tokens = Lexer::new(input).tokenize().unwrap();
             assert_eq!(tokens[0], expect);
         });
@@ -314,13 +848,87 @@ mod tests {
                 Token::String(r#"😄😇👺"#.to_string()),
             ),
         ];
-        string_strs.map(|(input, expect)| {
+        string_strs.into_iter().for_each(|(input, expect)| {
             let tokens = Lexer::new(input).tokenize().unwrap();
             assert_eq!(tokens[0], expect);
         });
 
-        let tokens = Lexer::new("\"hello world").tokenize().unwrap();
-        assert_eq!(tokens.len(), 0);
+        assert!(Lexer::new("\"hello world").tokenize().is_err());
+    }
+
+    #[test]
+    fn test_unicode_escape() {
+        let tokens = Lexer::new(r#""\u0041\u00e9""#).tokenize().unwrap();
+        assert_eq!(tokens[0], Token::String("Aé".to_string()));
+
+        // 16進数でない文字の位置を返す
+        let error = Lexer::new("[\n  \"\\u12G4\"]").tokenize().unwrap_err();
+        assert_eq!(error.msg, r"error: an invalid hex digit 'G' in a \u escape");
+        assert_eq!((error.line, error.column), (2, 8));
+
+        let error = Lexer::new(r#""\u12""#).tokenize().unwrap_err();
+        assert_eq!(
+            error.msg,
+            r#"error: an invalid hex digit '"' in a \u escape"#
+        );
+        assert_eq!((error.line, error.column), (1, 6));
+
+        let error = Lexer::new(r#""\u12"#).tokenize().unwrap_err();
+        assert_eq!(
+            error.msg,
+            r"error: a \u escape must be followed by 4 hex digits"
+        );
+        assert_eq!((error.line, error.column), (1, 6));
+    }
+
+    #[test]
+    fn test_surrogate_pairs() {
+        let tokens = Lexer::new(r#""\uD83D\uDE04abc\uD83D\uDC7A\u3042""#)
+            .tokenize()
+            .unwrap();
+        assert_eq!(tokens[0], Token::String("😄abc👺あ".to_string()));
+
+        let error = |input: &str| Lexer::new(input).tokenize().unwrap_err().msg;
+        // 高サロゲートだけ
+        assert_eq!(
+            error(r#""\uD800""#),
+            r"error: a high surrogate \uD800 must be followed by a low surrogate"
+        );
+        assert_eq!(
+            error(r#""\uD83Dabc""#),
+            r"error: a high surrogate \uD83D must be followed by a low surrogate"
+        );
+        assert_eq!(
+            error(r#""\uD83D\uD83D\uDE04""#),
+            r"error: a high surrogate \uD83D must be followed by a low surrogate"
+        );
+        assert_eq!(
+            error(r#""\uD83D\n""#),
+            r"error: a high surrogate \uD83D must be followed by a low surrogate"
+        );
+        // 低サロゲートだけ、もしくは順序が逆
+        assert_eq!(
+            error(r#""\uDC00""#),
+            r"error: a low surrogate \uDC00 must follow a high surrogate"
+        );
+        assert_eq!(
+            error(r#""\uDE04\uD83D""#),
+            r"error: a low surrogate \uDE04 must follow a high surrogate"
+        );
+    }
+
+    #[test]
+    fn test_escaped_string() {
+        let string_strs = [
+            (r#""\t""#, "\t"),
+            (r#""line1\nline2""#, "line1\nline2"),
+            (r#""quote\"inside""#, "quote\"inside"),
+            (r#""\\\/\b\f\r""#, "\\/\u{0008}\u{000C}\r"),
+        ];
+        string_strs.into_iter().for_each(|(input, expect)| {
+            let tokens = Lexer::new(input).tokenize().unwrap();
+            assert_eq!(tokens[0], Token::String(expect.to_string()));
+        });
     }
 
     #[test]
@@ -402,4 +1010,405 @@ mod tests {
             .zip(result_tokens.iter())
             .for_each(|(x, y)| assert_eq!(x, y));
     }
+
+    #[test]
+    fn test_max_tokens() {
+        let options = LexerOptions {
+            max_tokens: Some(5),
+            ..LexerOptions::default()
+        };
+        let tokens = Lexer::with_options("[1, 2]", options.clone())
+            .tokenize()
+            .unwrap();
+        assert_eq!(tokens.len(), 5);
+
+        let result = Lexer::with_options("[1, 2, 3]", options).tokenize();
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal() {
+        use rust_decimal::Decimal;
+        use std::str::FromStr;
+
+        let options = LexerOptions {
+            decimal_numbers: true,
+            ..LexerOptions::default()
+        };
+        let tokens = Lexer::with_options("[0.1, 0.2, 1e3]", options)
+            .tokenize()
+            .unwrap();
+        let (a, b) = match (&tokens[1], &tokens[3]) {
+            (Token::Decimal(a), Token::Decimal(b)) => (*a, *b),
+            _ => panic!("decimal tokens are expected {:?}", tokens),
+        };
+        assert_eq!(a + b, Decimal::from_str("0.3").unwrap());
+        assert_eq!((a + b).to_string(), "0.3");
+        assert_eq!(tokens[5], Token::Decimal(Decimal::from(1000)));
+    }
+
+    #[test]
+    fn test_normalize_negative_zero() {
+        let options = LexerOptions {
+            normalize_negative_zero: true,
+            ..LexerOptions::default()
+        };
+        let tokens = Lexer::with_options("[1.0, 1E0, 1, -0, -0.0e1]", options)
+            .tokenize()
+            .unwrap();
+        let spellings: Vec<String> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Number(n) => Some(crate::Value::Number(*n).to_json_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spellings, vec!["1", "1", "1", "0", "0"]);
+        assert!(matches!(tokens[7], Token::Number(n) if n.is_sign_positive()));
+
+        // 設定しない場合は -0 のまま。それ以外の数値は設定によらず値だけを持つ
+        let tokens = Lexer::new("[-0, 1.0, 1E0, 1e2, 1.50]").tokenize().unwrap();
+        assert!(matches!(tokens[1], Token::Number(n) if n.is_sign_negative()));
+        assert_eq!(
+            [3, 5, 7, 9].map(|i| tokens[i].clone()),
+            [1.0, 1.0, 100.0, 1.5].map(Token::Number)
+        );
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_normalize_decimal_numbers() {
+        let options = LexerOptions {
+            decimal_numbers: true,
+            normalize_negative_zero: true,
+            ..LexerOptions::default()
+        };
+        let tokens = Lexer::with_options("[1.0, 1E0, 1, 1.50, -0.0]", options)
+            .tokenize()
+            .unwrap();
+        let spellings: Vec<String> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Decimal(d) => Some(d.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spellings, vec!["1", "1", "1", "1.5", "0"]);
+    }
+
+    #[test]
+    fn test_number_underscores() {
+        let options = LexerOptions {
+            allow_number_underscores: true,
+            ..LexerOptions::default()
+        };
+        let tokenize = |input: &str| Lexer::with_options(input, options.clone()).tokenize();
+        assert_eq!(tokenize("1_000").unwrap(), vec![Token::Number(1000.0)]);
+        assert_eq!(
+            tokenize("-1_000_000.000_5e1_0").unwrap(),
+            vec![Token::Number(-1_000_000.000_5e10)]
+        );
+        for input in ["1__0", "1_", "-_1", "1_.5", "1._5", "1_e3", "1e_3"] {
+            let error = tokenize(input).unwrap_err();
+            assert!(error.msg.contains("between digits"), "{}", input);
+        }
+
+        // 設定しない場合は許可しない
+        assert!(Lexer::new("1_000").tokenize().is_err());
+    }
+
+    #[test]
+    fn test_bare_words() {
+        let options = LexerOptions {
+            allow_bare_words: true,
+            ..LexerOptions::default()
+        };
+        let tokens = Lexer::with_options(r#"{"status": ok}"#, options.clone())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LeftBrace,
+                Token::String("status".to_string()),
+                Token::Colon,
+                Token::String("ok".to_string()),
+                Token::RightBrace,
+            ]
+        );
+
+        let tokens = Lexer::with_options(
+            "[in_progress, true, null, nothing, True, v2]",
+            options.clone(),
+        )
+        .tokenize()
+        .unwrap();
+        assert_eq!(
+            tokens
+                .into_iter()
+                .filter(|token| *token != Token::Comma)
+                .collect::<Vec<_>>(),
+            vec![
+                Token::LeftBracket,
+                Token::String("in_progress".to_string()),
+                Token::Bool(true),
+                Token::Null,
+                Token::String("nothing".to_string()),
+                Token::String("True".to_string()),
+                Token::String("v2".to_string()),
+                Token::RightBracket,
+            ]
+        );
+
+        let value = crate::Parser::new(
+            Lexer::with_options(r#"{"status": ok}"#, options.clone())
+                .tokenize()
+                .unwrap(),
+        )
+        .into_value()
+        .unwrap();
+        assert_eq!(
+            value.get("status"),
+            Some(&crate::Value::String("ok".to_string()))
+        );
+
+        // 許可していない場合はエラー
+        assert!(Lexer::new(r#"{"status": ok}"#).tokenize().is_err());
+    }
+
+    #[test]
+    fn test_python_literals() {
+        let options = LexerOptions {
+            allow_python_literals: true,
+            ..LexerOptions::default()
+        };
+        let tokens = Lexer::with_options("[True, False, None, true, null]", options.clone())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LeftBracket,
+                Token::Bool(true),
+                Token::Comma,
+                Token::Bool(false),
+                Token::Comma,
+                Token::Null,
+                Token::Comma,
+                Token::Bool(true),
+                Token::Comma,
+                Token::Null,
+                Token::RightBracket,
+            ]
+        );
+        for input in ["TRUE", "Nonesuch", "Fals"] {
+            assert!(Lexer::with_options(input, options.clone())
+                .tokenize()
+                .is_err());
+        }
+
+        // 設定しない場合は許可しない
+        for input in ["True", "False", "None"] {
+            assert!(Lexer::new(input).tokenize().is_err());
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let json = "// head\n{\"a\": /* block\n comment */ 1}";
+        let options = LexerOptions {
+            allow_comments: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(json, options);
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LeftBrace,
+                Token::String("a".to_string()),
+                Token::Colon,
+                Token::Number(1.0),
+                Token::RightBrace,
+            ]
+        );
+        assert_eq!(
+            lexer.into_comments(),
+            vec![
+                Comment {
+                    kind: CommentKind::Line,
+                    text: " head".to_string(),
+                    position: 0,
+                },
+                Comment {
+                    kind: CommentKind::Block,
+                    text: " block\n comment ".to_string(),
+                    position: 14,
+                },
+            ]
+        );
+
+        // コメントを許可していない場合はエラー
+        assert!(Lexer::new("// head\n1").tokenize().is_err());
+
+        let options = LexerOptions {
+            allow_comments: true,
+            ..LexerOptions::default()
+        };
+        assert!(Lexer::with_options("1 /* open", options.clone())
+            .tokenize()
+            .is_err());
+        assert!(Lexer::with_options("1 / 2", options).tokenize().is_err());
+    }
+
+    #[test]
+    fn test_spanned_escaped_string() {
+        let mut lexer = Lexer::new(r#""\u3042\n" 1"#);
+        let (token, range) = lexer.next_spanned_token().unwrap().unwrap();
+        assert_eq!(token, Token::String("あ\n".to_string()));
+        assert_eq!(range, 0..10);
+        lexer.next_spanned_token().unwrap();
+        let (token, range) = lexer.next_spanned_token().unwrap().unwrap();
+        assert_eq!(token, Token::Number(1.0));
+        assert_eq!(range, 11..12);
+    }
+
+    #[test]
+    fn test_number_decimal_point() {
+        assert_eq!(
+            Lexer::new(".3").tokenize().unwrap(),
+            vec![Token::Number(0.3)]
+        );
+        assert_eq!(
+            Lexer::new("3.").tokenize().unwrap(),
+            vec![Token::Number(3.0)]
+        );
+        assert_eq!(
+            Lexer::new("-.5e1").tokenize().unwrap(),
+            vec![Token::Number(-5.0)]
+        );
+
+        for input in [".", "-.", ".e3", "[.]"] {
+            let error = Lexer::new(input).tokenize().unwrap_err();
+            assert!(
+                error
+                    .msg
+                    .starts_with("error: a \".\" in a number must have a digit"),
+                "{}",
+                error.msg
+            );
+        }
+    }
+
+    #[test]
+    fn test_tokenize_with_offsets() {
+        let json = r#"{"あい": [-1.5e3, "xyzw"]}"#;
+        let tokens = Lexer::new(json).tokenize_with_offsets().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::LeftBrace, 0..1),
+                (Token::String("あい".to_string()), 1..9),
+                (Token::Colon, 9..10),
+                (Token::LeftBracket, 11..12),
+                (Token::Number(-1500.0), 12..18),
+                (Token::Comma, 18..19),
+                (Token::String("xyzw".to_string()), 20..26),
+                (Token::RightBracket, 26..27),
+                (Token::RightBrace, 27..28),
+            ]
+        );
+        // 範囲は元の入力をそのまま切り出せるバイト位置
+        assert_eq!(&json[tokens[1].1.clone()], "\"あい\"");
+        assert_eq!(&json[tokens[4].1.clone()], "-1.5e3");
+        assert_eq!(&json[tokens[6].1.clone()], "\"xyzw\"");
+    }
+
+    #[test]
+    fn test_feed() {
+        let mut lexer = StreamLexer::new();
+        lexer.feed(r#"{"togat"#).unwrap();
+        assert_eq!(lexer.drain_tokens(), vec![Token::LeftBrace]);
+
+        lexer.feed(r#"oga": 12"#).unwrap();
+        assert_eq!(
+            lexer.drain_tokens(),
+            vec![Token::String("togatoga".to_string()), Token::Colon]
+        );
+
+        lexer.feed(r#"3.5, "\u30"#).unwrap();
+        assert_eq!(
+            lexer.drain_tokens(),
+            vec![Token::Number(123.5), Token::Comma]
+        );
+
+        lexer.feed(r#"42": tr"#).unwrap();
+        assert_eq!(
+            lexer.drain_tokens(),
+            vec![Token::String("あ".to_string()), Token::Colon]
+        );
+
+        lexer.feed("ue}").unwrap();
+        assert_eq!(
+            lexer.drain_tokens(),
+            vec![Token::Bool(true), Token::RightBrace]
+        );
+
+        // 入力の終わりで持ち越していた数値を読み込む
+        lexer.feed(" 42").unwrap();
+        assert_eq!(lexer.drain_tokens(), vec![]);
+        lexer.finish().unwrap();
+        assert_eq!(lexer.drain_tokens(), vec![Token::Number(42.0)]);
+
+        // 続きを待たなくても不正な文字はエラー
+        let mut lexer = StreamLexer::new();
+        assert!(lexer.feed("[1, x").is_err());
+
+        // 1 文字ずつ受け取っても同じ Token になる
+        let json = r#"{"a\u3042": [-12.5e1, "x\"y", true], "b": 1234567890}"#;
+        let mut lexer = StreamLexer::new();
+        for c in json.chars() {
+            lexer.feed(&c.to_string()).unwrap();
+        }
+        lexer.finish().unwrap();
+        assert_eq!(lexer.drain_tokens(), Lexer::new(json).tokenize().unwrap());
+
+        // 持ち越している文字列の途中で、不正なエスケープや制御文字はエラー
+        let mut lexer = StreamLexer::new();
+        lexer.feed(r#"["abc"#).unwrap();
+        assert!(lexer.feed("\\qrs").is_err());
+        let mut lexer = StreamLexer::new();
+        lexer.feed(r#"["abc"#).unwrap();
+        assert!(lexer.feed("\\u00").is_ok());
+        assert!(lexer.feed("zz").is_err());
+    }
+
+    #[test]
+    fn test_line_column() {
+        let input = "{\n  \"あ\": 1\n}";
+        assert_eq!(line_column(input, 0), (1, 1));
+        assert_eq!(line_column(input, 4), (2, 3));
+        // "あ" は 3 バイトだが 1 文字として数える
+        assert_eq!(line_column(input, 11), (2, 8));
+        assert_eq!(line_column(input, 13), (3, 1));
+    }
+
+    #[test]
+    fn test_error_position() {
+        // 閉じていない文字列は開始位置の " を指す
+        let error = Lexer::new("{\n  \"a\": 1,\n  \"b\": \"あいう")
+            .tokenize()
+            .unwrap_err();
+        assert_eq!(error.msg, "error: a string is not terminated");
+        assert_eq!((error.line, error.column), (3, 8));
+
+        let error = Lexer::new("[\n  1,\n  2,\n  x\n]").tokenize().unwrap_err();
+        assert_eq!(error.msg, "error: an unexpected char x");
+        assert_eq!((error.line, error.column), (4, 3));
+
+        // 列は文字単位で数える
+        let error = Lexer::new("[\"あい\", x]").tokenize().unwrap_err();
+        assert_eq!((error.line, error.column), (1, 8));
+    }
 }
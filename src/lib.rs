@@ -1,30 +1,319 @@
-use lexer::Lexer;
-use parser::{Parser, ParserError};
+pub use diff::{Change, DiffCounts};
+pub use error::{JsonError, ValueError};
+pub use from_json::FromJson;
+pub use highlight::{highlight, TokenClass};
+use lexer::line_column;
+pub use lexer::{Comment, CommentKind, Lexer, LexerError, LexerOptions, StreamLexer, Token};
+pub use parser::{Parser, ParserError, ParserOptions, PartialValue};
+pub use reader::JsonReader;
+pub use serializer::{LineEnding, SerializeOptions};
+pub use validate::validate_with_limits;
+pub use value::{ArrayMergeStrategy, NonFinitePolicy, SanitizeOptions, SqlDialect};
 // BTreeMap はキーによってソートされた状態で値が格納される
 // HashMap はそうではない。
 use std::collections::BTreeMap;
 
+mod convert;
+mod diff;
+mod error;
+mod flatten;
+mod from_json;
+mod highlight;
 mod lexer;
+#[cfg(feature = "msgpack")]
+mod msgpack;
 mod parser;
+mod pointer;
+mod reader;
+#[cfg(feature = "serde_json")]
+mod serde_bridge;
+mod serializer;
+mod validate;
+mod value;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Value {
-    String(String),                  // 文字列
-    Number(f64),                     // 数値
-    Bool(bool),                      // 真偽値
-    Null,                            // Null
-    Array(Vec<Value>),               // JSON Array
+    String(String), // 文字列
+    Number(f64),    // 数値
+    #[cfg(feature = "decimal")]
+    Decimal(rust_decimal::Decimal), // 任意精度の数値 (decimal feature)
+    Bool(bool),     // 真偽値
+    Null,           // Null
+    Array(Vec<Value>), // JSON Array
     Object(BTreeMap<String, Value>), // JSON Object
+    /// シリアライズ済みの JSON 文字列。シリアライザはそのまま出力する。Value::raw で作る。
+    /// 比較やハッシュ値、get や pointer、iter などの読み込みの操作では Parse した Value として扱う。
+    /// 木を変更する操作 (iter_mut や pointer_mut など) では、先に Parse した Value に置き換えてから変更する
+    Raw(RawJson),
+}
+
+/// シリアライズ済みの JSON 文字列と、それを Parse した Value。
+/// 正しい JSON であることを保証するため、Value::raw でだけ作れる
+#[derive(Debug, Clone)]
+pub struct RawJson {
+    text: String,
+    value: Box<Value>,
+}
+
+impl RawJson {
+    /// シリアライズ済みの JSON 文字列を返す
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Parse した Value を返す
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// RawJson を消費して、Parse した Value を返す
+    pub fn into_value(self) -> Value {
+        *self.value
+    }
+}
+
+/// Raw は中身を Parse した Value として比較する (e.g. Raw("[1]") と Array([Number(1)]) は等しい)
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            #[cfg(feature = "decimal")]
+            (Value::Decimal(a), Value::Decimal(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Object(a), Value::Object(b)) => a == b,
+            (Value::Raw(raw), value) | (value, Value::Raw(raw)) => raw.value() == value,
+            _ => false,
+        }
+    }
 }
 
-/// 入力のJSON文字列から Value を返す
+impl Value {
+    /// シリアライズ済みの JSON 文字列から Raw を作る。JSON として不正な場合はエラーを返す
+    pub fn raw(json: &str) -> Result<Value, ParserError> {
+        Ok(Value::Raw(RawJson {
+            text: json.to_string(),
+            value: Box::new(parse(json)?),
+        }))
+    }
+}
+
+/// 入力のJSON文字列から Value を返す。
+/// ルートの Value の後ろに Token が残っている場合はエラーを返す。
+/// エラーにはエラーになった位置の行と列が設定される
 pub fn parse(input: &str) -> Result<Value, ParserError> {
-    match Lexer::new(input).tokenize() {
-        Ok(tokens) => Parser::new(tokens).parse(),
-        Err(e) => Err(ParserError::new(&e.msg)),
+    match Lexer::new(input).tokenize_with_offsets() {
+        Ok(tokens) => Parser::with_spans(tokens, ParserOptions::default())
+            .into_value()
+            .map_err(|e| e.locate_in(input)),
+        Err(e) => Err(e.into()),
     }
 }
 
+/// コメントを含む JSON 文字列から Value と、入力中のコメントの一覧を返す
+pub fn parse_with_comments(input: &str) -> Result<(Value, Vec<Comment>), JsonError> {
+    let options = LexerOptions {
+        allow_comments: true,
+        ..LexerOptions::default()
+    };
+    let mut lexer = Lexer::with_options(input, options);
+    let tokens = lexer.tokenize_with_offsets()?;
+    let value = Parser::with_spans(tokens, ParserOptions::default())
+        .into_value()
+        .map_err(|e| e.locate_in(input))?;
+    Ok((value, lexer.into_comments()))
+}
+
+/// 途中で途切れた JSON 文字列から、Parse できた部分の Value と途切れていたかを返す。
+/// 完全な JSON 文字列の場合は parse と同じ Value を返す。
+/// 末尾の途中までしかないかもしれない Token (e.g. 閉じていない文字列や、続きがあるかもしれない数値) は取り除き、
+/// 閉じていない Array/Object はそこまでの要素だけを持つ。
+/// 途切れた部分より前に誤りがある場合 (取り除いた Token の位置の誤りを含む) や、
+/// ルートの Value が閉じた後ろに記述が残っている場合は parse と同じエラーを返す
+pub fn parse_partial(input: &str) -> Result<(Value, bool), JsonError> {
+    let error = match parse(input) {
+        Ok(value) => return Ok((value, false)),
+        Err(error) => error,
+    };
+
+    // 末尾に接している Token は続きがあるかもしれないので tail として取り除く
+    let mut lexer = Lexer::new(input);
+    let mut tokens = vec![];
+    let mut tail = None;
+    loop {
+        match lexer.next_spanned_token() {
+            Ok(Some((Token::WhiteSpace, _))) => {}
+            Ok(Some((token, span))) => tokens.push((token, span)),
+            Ok(None) => {
+                let incomplete = matches!(
+                    tokens.last(),
+                    Some((token, span)) if span.end == input.len()
+                        && !matches!(
+                            token,
+                            Token::String(_)
+                                | Token::LeftBrace
+                                | Token::RightBrace
+                                | Token::LeftBracket
+                                | Token::RightBracket
+                                | Token::Comma
+                                | Token::Colon
+                        )
+                );
+                if incomplete {
+                    tail = tokens.pop().map(|(token, _)| token);
+                }
+                break;
+            }
+            // 末尾まで読んで失敗した Token は、途中で途切れた Token として扱う。
+            // 文法の確認には、同じ種類の Token (文字列かそれ以外の Value) を代わりに使う
+            Err(_) if lexer.offset() == input.len() => {
+                let start = tokens.last().map_or(0, |(_, span)| span.end);
+                tail = Some(if input[start..].trim_start().starts_with('"') {
+                    Token::String(String::new())
+                } else {
+                    Token::Null
+                });
+                break;
+            }
+            Err(_) => return Err(error.into()),
+        }
+    }
+
+    // 取り除いた Token も含めて、途切れた位置まで文法に従っているかを確かめる
+    let mut checked = tokens
+        .iter()
+        .map(|(token, _)| token.clone())
+        .collect::<Vec<_>>();
+    checked.extend(tail);
+    if !matches!(
+        Parser::new(checked).parse_with_budget(usize::MAX),
+        Ok(PartialValue {
+            resume_at: Some(_),
+            ..
+        })
+    ) {
+        return Err(error.into());
+    }
+
+    match Parser::with_spans(tokens, ParserOptions::default()).parse_with_budget(usize::MAX) {
+        Ok(PartialValue {
+            value: Some(value),
+            resume_at: Some(_),
+        }) => Ok((value, true)),
+        _ => Err(error.into()),
+    }
+}
+
+/// JSON 文字列のうち、JSON Pointer (e.g. "/meta/id") が指す Value だけを組み立てて返す。
+/// 経路上にない Value は組み立てずに読み飛ばす。
+/// 指す先が存在しない場合は None を返す。指す先より後ろの記述は検証しない
+pub fn extract(input: &str, pointer: &str) -> Result<Option<Value>, JsonError> {
+    /// tokens の順にたどり、指す先の Value を組み立てる
+    fn seek(parser: &mut Parser, tokens: Vec<String>) -> Result<Option<Value>, ParserError> {
+        for token in tokens {
+            let found = match parser.peek() {
+                Some(Token::LeftBrace) => parser.seek_key(&token)?,
+                Some(Token::LeftBracket) => match pointer::parse_index(&token) {
+                    Some(index) => parser.seek_index(index)?,
+                    None => false,
+                },
+                _ => false,
+            };
+            if !found {
+                return Ok(None);
+            }
+        }
+        parser.parse().map(Some)
+    }
+
+    let tokens = pointer::split_pointer(pointer)
+        .ok_or_else(|| ValueError::new(&format!("error: an invalid JSON Pointer {:?}", pointer)))?;
+    // 指す先を読み終えた時点で字句解析もやめる
+    let mut parser = Parser::from_lexer(Lexer::new(input), ParserOptions::default());
+    seek(&mut parser, tokens).map_err(|e| match parser.take_lexer_error() {
+        Some(e) => JsonError::from(e),
+        None => e.locate_in(input).into(),
+    })
+}
+
+/// parse_with_whitespace の結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseResult {
+    /// Parse した Value
+    pub value: Value,
+    /// ルートの Value の前にあった空白 (無い場合は空文字列)
+    pub leading_whitespace: String,
+    /// ルートの Value の後にあった空白 (無い場合は空文字列)
+    pub trailing_whitespace: String,
+}
+
+impl ParseResult {
+    /// ルートの Value の前後に空白があったか
+    pub fn has_surrounding_whitespace(&self) -> bool {
+        !self.leading_whitespace.is_empty() || !self.trailing_whitespace.is_empty()
+    }
+}
+
+/// JSON 文字列から Value と、ルートの Value の前後にあった空白を返す。
+/// 前後の空白を元のまま書き戻す (e.g. 差分の少ない整形) ために使う
+pub fn parse_with_whitespace(input: &str) -> Result<ParseResult, JsonError> {
+    let tokens = Lexer::new(input).tokenize_with_offsets()?;
+    let start = tokens.first().map_or(input.len(), |(_, range)| range.start);
+    let end = tokens.last().map_or(input.len(), |(_, range)| range.end);
+    let value = Parser::with_spans(tokens, ParserOptions::default())
+        .into_value()
+        .map_err(|e| e.locate_in(input))?;
+    Ok(ParseResult {
+        value,
+        leading_whitespace: input[..start].to_string(),
+        trailing_whitespace: input[end..].to_string(),
+    })
+}
+
+/// 寛容な設定で受け入れた、標準の JSON ではない記述
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// 受け入れた記述の説明
+    pub msg: String,
+    /// 行 (1 から始まる)
+    pub line: usize,
+    /// 列 (1 から始まる)
+    pub column: usize,
+}
+
+/// 設定に従って JSON 文字列から Value を返し、
+/// 寛容な設定 (コメントや末尾の , など) で受け入れた記述の一覧を出現順に返す。
+/// 既定で受け入れる標準ではない数値 (e.g. +1, .5, 01) も含む。
+/// ルートの Value の後ろに Token が残っている場合はエラーを返す
+pub fn parse_with_warnings(
+    input: &str,
+    lexer_options: LexerOptions,
+    parser_options: ParserOptions,
+) -> Result<(Value, Vec<Warning>), JsonError> {
+    let mut lexer = Lexer::with_options(input, lexer_options);
+    let tokens = lexer.tokenize_with_offsets()?;
+    let mut parser = Parser::with_spans(tokens, parser_options);
+    let value = parser
+        .parse()
+        .and_then(|value| parser.expect_end().map(|_| value))
+        .map_err(|e| e.locate_in(input))?;
+
+    let mut warnings = lexer.take_warnings();
+    warnings.extend(parser.take_warnings());
+    warnings.sort_by_key(|(_, offset)| *offset);
+
+    let warnings = warnings
+        .into_iter()
+        .map(|(msg, offset)| {
+            let (line, column) = line_column(input, offset);
+            Warning { msg, line, column }
+        })
+        .collect();
+    Ok((value, warnings))
+}
+
 /// {"key": true}
 /// v["key"] => Value::Bool(true)
 impl std::ops::Index<&str> for Value {
@@ -56,3 +345,129 @@ impl std::ops::Index<usize> for Value {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        extract, parse, parse_partial, parse_with_comments, parse_with_warnings,
+        parse_with_whitespace, JsonError, LexerOptions, ParserOptions, Value,
+    };
+
+    #[test]
+    fn test_parse_trailing_tokens() {
+        assert_eq!(
+            parse("1 2").unwrap_err().msg,
+            "error: unexpected trailing token Number(2.0)"
+        );
+        assert_eq!(
+            parse("{} []").unwrap_err().msg,
+            "error: unexpected trailing token LeftBracket"
+        );
+        assert!(parse("{} garbage").is_err());
+
+        assert_eq!(
+            parse(" {\"a\": [1, 2]} ").unwrap(),
+            Value::Object(
+                [(
+                    "a".to_string(),
+                    Value::Array(vec![Value::Number(1.0), Value::Number(2.0)])
+                )]
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let error = parse("{\n  \"a\": [1, 2}\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 13));
+        let error = parse("{\n  \"a\": tru\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 8));
+    }
+
+    #[test]
+    fn test_error_position_in_other_entry_points() {
+        assert_eq!(
+            parse_with_comments("// c\n[1, 2").unwrap_err().to_string(),
+            "error at line 2, column 5: a token isn't peekable"
+        );
+        assert_eq!(
+            parse_partial("[1,\n 2 3").unwrap_err().to_string(),
+            "error at line 2, column 4: a | or, token is expected Number(3.0)"
+        );
+        assert_eq!(
+            parse_with_whitespace(" [1,\n :]").unwrap_err().to_string(),
+            "error at /1 (line 2, column 2): a token must start { or [ or string or number or bool or null Colon"
+        );
+        assert_eq!(
+            extract("{\n\"a\" 1}", "/a").unwrap_err().to_string(),
+            "error at line 2, column 5: a token must be Colon Number(1.0)"
+        );
+        assert_eq!(
+            parse_with_warnings("[1,\n}", LexerOptions::default(), ParserOptions::default())
+                .unwrap_err()
+                .to_string(),
+            "error at /1 (line 2, column 1): a token must start { or [ or string or number or bool or null RightBrace"
+        );
+    }
+
+    #[test]
+    fn test_extract() {
+        let items: Vec<String> = (0..1000)
+            .map(|i| format!(r#"{{"id": {}, "tags": ["a", {{"b": [{}]}}]}}"#, i, i))
+            .collect();
+        let json = format!(
+            r#"{{"items": [{}], "meta": {{"count": 1000, "id": "abc"}}, "rest": [1, 2]}}"#,
+            items.join(",")
+        );
+        assert_eq!(
+            extract(&json, "/meta/id").unwrap(),
+            Some(Value::String("abc".to_string()))
+        );
+        assert_eq!(
+            extract(&json, "/items/999/tags/1").unwrap(),
+            Some(parse(r#"{"b": [999]}"#).unwrap())
+        );
+        assert_eq!(extract(&json, "").unwrap(), Some(parse(&json).unwrap()));
+
+        assert_eq!(extract(&json, "/meta/missing").unwrap(), None);
+        assert_eq!(extract(&json, "/items/1000").unwrap(), None);
+        assert_eq!(extract(&json, "/items/x").unwrap(), None);
+        assert_eq!(extract(&json, "/meta/id/0").unwrap(), None);
+        assert!(extract(&json, "meta").is_err());
+        assert!(extract(r#"{"a" 1}"#, "/a").is_err());
+
+        // 指す先より後ろは字句解析もしないので、不正な記述があっても読み出せる
+        assert_eq!(
+            extract(r#"{"a": [1, 2], "b": tru"#, "/a/1").unwrap(),
+            Some(Value::Number(2.0))
+        );
+        // 指す先より前の字句解析のエラーは、位置付きの Lexer のエラーになる
+        match extract("{\"b\": tru,\n \"a\": 1}", "/a").unwrap_err() {
+            JsonError::Lexer(e) => assert_eq!((e.line, e.column), (1, 7)),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_parse_with_whitespace() {
+        let result = parse_with_whitespace("\n  [1, 2] \t\n").unwrap();
+        assert_eq!(result.value, parse("[1, 2]").unwrap());
+        assert_eq!(result.leading_whitespace, "\n  ");
+        assert_eq!(result.trailing_whitespace, " \t\n");
+        assert!(result.has_surrounding_whitespace());
+
+        let result = parse_with_whitespace(r#"{"a": 1}"#).unwrap();
+        assert_eq!(result.leading_whitespace, "");
+        assert_eq!(result.trailing_whitespace, "");
+        assert!(!result.has_surrounding_whitespace());
+
+        let result = parse_with_whitespace("null\n").unwrap();
+        assert_eq!(result.value, Value::Null);
+        assert_eq!(result.leading_whitespace, "");
+        assert_eq!(result.trailing_whitespace, "\n");
+
+        assert!(parse_with_whitespace("  ").is_err());
+        assert!(parse_with_whitespace("1 2").is_err());
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{JsonError, Value, ValueError};
+use std::collections::BTreeMap;
+
+/// MessagePack のバイト列を読み込む
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    /// bytes の先頭
+    index: usize,
+}
+
+impl<'a> Decoder<'a> {
+    /// n バイト読み進め、読み込んだバイト列を返す
+    fn take(&mut self, n: usize) -> Result<&'a [u8], JsonError> {
+        let end = self
+            .index
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| ValueError::new("error: unexpected end of MessagePack data"))?;
+        let bytes = &self.bytes[self.index..end];
+        self.index = end;
+        Ok(bytes)
+    }
+
+    /// n バイトのビッグエンディアンの符号なし整数を読み込む
+    fn read_uint(&mut self, n: usize) -> Result<u64, JsonError> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .fold(0, |acc, byte| (acc << 8) | u64::from(*byte)))
+    }
+
+    /// n バイトのビッグエンディアンの符号付き整数を読み込む
+    fn read_int(&mut self, n: usize) -> Result<i64, JsonError> {
+        let shift = 64 - 8 * n as u32;
+        Ok(((self.read_uint(n)? << shift) as i64) >> shift)
+    }
+
+    /// 長さ len の文字列を読み込む
+    fn read_str(&mut self, len: usize) -> Result<Value, JsonError> {
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map(Value::String)
+            .map_err(|e| ValueError::new(&format!("error: {}", e)).into())
+    }
+
+    /// 要素数 len の Array を読み込む
+    fn read_array(&mut self, len: usize) -> Result<Value, JsonError> {
+        (0..len)
+            .map(|_| self.read_value())
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array)
+    }
+
+    /// 要素数 len の Map を Object として読み込む。キーは文字列のみ許可する
+    fn read_map(&mut self, len: usize) -> Result<Value, JsonError> {
+        let mut object = BTreeMap::new();
+        for _ in 0..len {
+            let key = match self.read_value()? {
+                Value::String(key) => key,
+                key => {
+                    return Err(ValueError::new(&format!(
+                        "error: a MessagePack map key must be a string {:?}",
+                        key
+                    ))
+                    .into())
+                }
+            };
+            object.insert(key, self.read_value()?);
+        }
+        Ok(Value::Object(object))
+    }
+
+    /// 先頭の型を表すバイトに応じて Value を読み込む
+    fn read_value(&mut self) -> Result<Value, JsonError> {
+        let marker = self.take(1)?[0];
+        match marker {
+            0x00..=0x7f => Ok(Value::Number(f64::from(marker))),
+            0x80..=0x8f => self.read_map(usize::from(marker & 0x0f)),
+            0x90..=0x9f => self.read_array(usize::from(marker & 0x0f)),
+            0xa0..=0xbf => self.read_str(usize::from(marker & 0x1f)),
+            0xc0 => Ok(Value::Null),
+            0xc2 => Ok(Value::Bool(false)),
+            0xc3 => Ok(Value::Bool(true)),
+            0xca => Ok(Value::Number(f64::from(f32::from_bits(
+                self.read_uint(4)? as u32
+            )))),
+            0xcb => Ok(Value::Number(f64::from_bits(self.read_uint(8)?))),
+            0xcc => Ok(Value::Number(self.read_uint(1)? as f64)),
+            0xcd => Ok(Value::Number(self.read_uint(2)? as f64)),
+            0xce => Ok(Value::Number(self.read_uint(4)? as f64)),
+            0xcf => Ok(Value::Number(self.read_uint(8)? as f64)),
+            0xd0 => Ok(Value::Number(self.read_int(1)? as f64)),
+            0xd1 => Ok(Value::Number(self.read_int(2)? as f64)),
+            0xd2 => Ok(Value::Number(self.read_int(4)? as f64)),
+            0xd3 => Ok(Value::Number(self.read_int(8)? as f64)),
+            0xd9 => {
+                let len = self.read_uint(1)? as usize;
+                self.read_str(len)
+            }
+            0xda => {
+                let len = self.read_uint(2)? as usize;
+                self.read_str(len)
+            }
+            0xdb => {
+                let len = self.read_uint(4)? as usize;
+                self.read_str(len)
+            }
+            0xdc => {
+                let len = self.read_uint(2)? as usize;
+                self.read_array(len)
+            }
+            0xdd => {
+                let len = self.read_uint(4)? as usize;
+                self.read_array(len)
+            }
+            0xde => {
+                let len = self.read_uint(2)? as usize;
+                self.read_map(len)
+            }
+            0xdf => {
+                let len = self.read_uint(4)? as usize;
+                self.read_map(len)
+            }
+            0xe0..=0xff => Ok(Value::Number(f64::from(marker as i8))),
+            // bin や ext は JSON で表現できない
+            _ => Err(ValueError::new(&format!(
+                "error: an unsupported MessagePack type 0x{:02x}",
+                marker
+            ))
+            .into()),
+        }
+    }
+}
+
+/// 型を表すバイトと、長さ len をビッグエンディアンで書き込む
+fn write_len(buffer: &mut Vec<u8>, markers: [u8; 3], len: usize) {
+    if len <= 0xff && markers[0] != 0 {
+        buffer.push(markers[0]);
+        buffer.push(len as u8);
+    } else if len <= 0xffff {
+        buffer.push(markers[1]);
+        buffer.extend((len as u16).to_be_bytes());
+    } else {
+        buffer.push(markers[2]);
+        buffer.extend((len as u32).to_be_bytes());
+    }
+}
+
+/// 数値を書き込む。i64 で表せる整数は整数の形式で、それ以外は float 64 で書き込む
+fn write_number(buffer: &mut Vec<u8>, n: f64) {
+    if n.fract() != 0.0 || !(-9.2e18..9.2e18).contains(&n) || (n == 0.0 && n.is_sign_negative()) {
+        buffer.push(0xcb);
+        buffer.extend(n.to_bits().to_be_bytes());
+        return;
+    }
+    match n as i64 {
+        i @ 0..=0x7f => buffer.push(i as u8),
+        i @ -32..=-1 => buffer.push(i as u8),
+        i @ 0..=0xff => buffer.extend([0xcc, i as u8]),
+        i @ 0..=0xffff => {
+            buffer.push(0xcd);
+            buffer.extend((i as u16).to_be_bytes());
+        }
+        i @ 0..=0xffff_ffff => {
+            buffer.push(0xce);
+            buffer.extend((i as u32).to_be_bytes());
+        }
+        i @ -0x80..=-1 => buffer.extend([0xd0, i as u8]),
+        i @ -0x8000..=-1 => {
+            buffer.push(0xd1);
+            buffer.extend((i as i16).to_be_bytes());
+        }
+        i @ -0x8000_0000..=-1 => {
+            buffer.push(0xd2);
+            buffer.extend((i as i32).to_be_bytes());
+        }
+        i => {
+            buffer.push(0xd3);
+            buffer.extend(i.to_be_bytes());
+        }
+    }
+}
+
+/// 文字列を書き込む
+fn write_str(buffer: &mut Vec<u8>, s: &str) {
+    if s.len() < 32 {
+        buffer.push(0xa0 | s.len() as u8);
+    } else {
+        write_len(buffer, [0xd9, 0xda, 0xdb], s.len());
+    }
+    buffer.extend(s.as_bytes());
+}
+
+/// Value を再帰的に書き込む
+fn write_value(buffer: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::String(s) => write_str(buffer, s),
+        Value::Number(n) => write_number(buffer, *n),
+        // MessagePack には任意精度の数値が無いので f64 に変換する
+        #[cfg(feature = "decimal")]
+        Value::Decimal(d) => write_number(buffer, d.to_string().parse().unwrap_or(f64::NAN)),
+        Value::Bool(false) => buffer.push(0xc2),
+        Value::Bool(true) => buffer.push(0xc3),
+        Value::Null => buffer.push(0xc0),
+        // Parse した Value として書き込む
+        Value::Raw(raw) => write_value(buffer, raw.value()),
+        Value::Array(array) => {
+            if array.len() < 16 {
+                buffer.push(0x90 | array.len() as u8);
+            } else {
+                write_len(buffer, [0, 0xdc, 0xdd], array.len());
+            }
+            array.iter().for_each(|v| write_value(buffer, v));
+        }
+        Value::Object(object) => {
+            if object.len() < 16 {
+                buffer.push(0x80 | object.len() as u8);
+            } else {
+                write_len(buffer, [0, 0xde, 0xdf], object.len());
+            }
+            object.iter().for_each(|(k, v)| {
+                write_str(buffer, k);
+                write_value(buffer, v);
+            });
+        }
+    }
+}
+
+impl Value {
+    /// MessagePack のバイト列に変換する。
+    /// 整数として表せる数値は整数の形式に、それ以外の数値は float 64 になる
+    pub fn to_msgpack(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        write_value(&mut buffer, self);
+        buffer
+    }
+
+    /// MessagePack のバイト列から Value を返す。
+    /// キーが文字列でない Map や、bin/ext などの JSON で表現できない型はエラーになる
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Value, JsonError> {
+        let mut decoder = Decoder { bytes, index: 0 };
+        let value = decoder.read_value()?;
+        if decoder.index != bytes.len() {
+            return Err(ValueError::new("error: unexpected trailing MessagePack data").into());
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, Value};
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let long_string = "x".repeat(300);
+        let json = format!(
+            r#"{{"int": [0, 127, 128, 255, 65536, 5000000000, -1, -32, -33, -200, -40000, -3000000000],
+                "float": [0.5, -1.25, 1e300],
+                "bool": [true, false], "null": null,
+                "string": ["", "あいう", "{}"],
+                "nested": {{"a": [[], {{}}], "b": [1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17]}}}}"#,
+            long_string
+        );
+        let value = parse(&json).unwrap();
+        let bytes = value.to_msgpack();
+        assert_eq!(Value::from_msgpack(&bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn test_msgpack_bytes() {
+        let value = parse(r#"{"a": [1, -1, true, null, 1.5]}"#).unwrap();
+        assert_eq!(
+            value.to_msgpack(),
+            vec![
+                0x81, 0xa1, b'a', 0x95, 0x01, 0xff, 0xc3, 0xc0, 0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0
+            ]
+        );
+
+        // 途中で終わっている
+        assert!(Value::from_msgpack(&[0x92, 0x01]).is_err());
+        // キーが文字列ではない
+        assert!(Value::from_msgpack(&[0x81, 0x01, 0x02]).is_err());
+        // bin は未対応
+        assert!(Value::from_msgpack(&[0xc4, 0x01, 0x00]).is_err());
+        assert!(Value::from_msgpack(&[0xc0, 0xc0]).is_err());
+    }
+}
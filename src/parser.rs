@@ -1,29 +1,177 @@
-use crate::{lexer::Token, Value};
+use crate::{
+    lexer::{line_column, Lexer, LexerError, Token},
+    pointer::escape_token,
+    Value,
+};
+use std::ops::Range;
+
+/// Array の要素数を数えるために先読みする Token の最大数
+const ARRAY_SCAN_LIMIT: usize = 1024;
 
 #[derive(Debug, Clone)]
 pub struct ParserError {
     pub msg: String,
+    /// エラーが発生した位置を表す JSON Pointer (e.g. /users/2/name)。トップレベルの場合は空文字列
+    pub path: String,
+    /// エラーが発生するまでに読み込んだ Token の数
+    pub consumed: usize,
+    /// エラーになった Token の開始位置 (入力文字列に対するバイト単位の位置、分からない場合は None)
+    pub offset: Option<usize>,
+    /// エラーになった Token の行 (1 から始まる、分からない場合は 0)
+    pub line: usize,
+    /// エラーになった Token の列 (1 から始まる、分からない場合は 0)
+    pub column: usize,
+    /// path の参照トークンを内側から順に並べたもの (path は locate でまとめて組み立てる)
+    segments: Vec<String>,
 }
 
 impl ParserError {
     pub fn new(msg: &str) -> ParserError {
         ParserError {
             msg: msg.to_string(),
+            path: String::new(),
+            consumed: 0,
+            offset: None,
+            line: 0,
+            column: 0,
+            segments: vec![],
         }
     }
+
+    /// offset を入力文字列 input の行と列に変換して設定する
+    pub(crate) fn locate_in(mut self, input: &str) -> ParserError {
+        if let Some(offset) = self.offset {
+            (self.line, self.column) = line_column(input, offset);
+        }
+        self
+    }
+
+    /// 親の Array/Object から見た位置 (Array の添字か Object のキー) を path の先頭に追加する
+    fn with_parent(mut self, segment: &str) -> ParserError {
+        self.segments.push(escape_token(segment));
+        self
+    }
 }
 
-pub struct Parser {
+pub struct Parser<'a> {
     /// Lexer で tokenize した Token
     tokens: Vec<Token>,
+    /// tokens の続きを必要になった時点で読み込む Lexer (無い場合は tokens が全て)
+    lexer: Option<Lexer<'a>>,
+    /// lexer が失敗した場合のエラー
+    lexer_error: Option<LexerError>,
     /// tokens の先頭
     index: usize,
+    /// 現在の parse を始めた位置 (トップレベルの Value の先頭)
+    root: usize,
+    /// 読み込み可能な Token の終端 (この位置以降の Token は存在しないものとして扱う)。
+    /// parse_with_budget の間だけ設定する
+    limit: usize,
+    /// Token が途中で尽きた場合に、そこまでの Value を返すか
+    partial: bool,
+    /// partial の場合に Token が途中で尽きたか
+    truncated: bool,
+    /// 構文解析の設定
+    options: ParserOptions,
+    /// 各 Token の入力文字列に対する位置 (分からない場合は空)
+    spans: Vec<Range<usize>>,
+    /// 寛容な設定で受け入れた記述の説明と、その位置 (バイト単位)
+    warnings: Vec<(String, usize)>,
+    /// 最後に参照した Token の位置 (エラーの位置を求めるために使う)
+    last: usize,
+    /// 組み立てた Value の大きさの合計 (バイト単位、max_total_bytes のために数える)
+    allocated: usize,
+}
+
+/// 構文解析の設定
+#[derive(Debug, Clone, Default)]
+pub struct ParserOptions {
+    /// トップレベルの Value を Object か Array に限定する (RFC 4627 の規則)。
+    /// false の場合は RFC 8259 に従い、文字列や数値などもトップレベルに置ける
+    pub require_root_container: bool,
+    /// Array/Object の最後の要素の後ろの , (e.g. [1, 2,]) を許可する
+    pub allow_trailing_commas: bool,
+    /// Object のキーの最大の長さ (バイト単位)。超えるキーがあればエラーにする。
+    /// Lexer がキーを String として読み込んだ後に確かめる意味上の制限なので、読み込みに使うメモリは制限しない。
+    /// メモリを制限したい場合は、Lexer に渡す前に入力全体の大きさを制限すること
+    pub max_key_len: Option<usize>,
+    /// 組み立てる Value の大きさの合計の上限 (バイト単位)。超えた時点で Parse を中断する。
+    /// 各 Value の大きさ (size_of::<Value>()) と、文字列およびキーのバイト数の合計で概算する
+    pub max_total_bytes: Option<usize>,
+}
+
+/// 途中までの Parse 結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialValue {
+    /// Parse できた部分の Value。
+    /// 途中で Token が尽きた Array/Object はそこまでの要素だけを持ち、値が揃っていない Object のキーは含まない
+    pub value: Option<Value>,
+    /// 途中で止まった (budget を使い切った、もしくは Token が途中で尽きた) 場合に、読み込めた Token の終端の位置
+    pub resume_at: Option<usize>,
 }
 
-impl Parser {
+impl<'a> Parser<'a> {
     /// Token の一覧を受け取り Parser を返す
-    pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser { tokens, index: 0 }
+    pub fn new(tokens: Vec<Token>) -> Parser<'a> {
+        Self::with_options(tokens, ParserOptions::default())
+    }
+
+    /// Token の一覧と設定を受け取り Parser を返す
+    pub fn with_options(tokens: Vec<Token>, options: ParserOptions) -> Parser<'a> {
+        Parser {
+            tokens,
+            lexer: None,
+            lexer_error: None,
+            index: 0,
+            root: 0,
+            limit: usize::MAX,
+            partial: false,
+            truncated: false,
+            options,
+            spans: vec![],
+            warnings: vec![],
+            last: 0,
+            allocated: 0,
+        }
+    }
+
+    /// 位置付きの Token の一覧と設定を受け取り Parser を返す
+    pub fn with_spans(tokens: Vec<(Token, Range<usize>)>, options: ParserOptions) -> Parser<'a> {
+        let (tokens, spans) = tokens.into_iter().unzip();
+        Parser {
+            spans,
+            ..Self::with_options(tokens, options)
+        }
+    }
+
+    /// Lexer と設定を受け取り Parser を返す。
+    /// Token は必要になった時点で読み込むので、Parse を途中でやめた場合は残りの入力を字句解析しない
+    pub(crate) fn from_lexer(lexer: Lexer<'a>, options: ParserOptions) -> Parser<'a> {
+        Parser {
+            lexer: Some(lexer),
+            ..Self::with_options(vec![], options)
+        }
+    }
+
+    /// Lexer から読み込んでいる場合に、字句解析のエラーで Token が尽きたならそのエラーを取り出す
+    pub(crate) fn take_lexer_error(&mut self) -> Option<LexerError> {
+        self.lexer_error.take()
+    }
+
+    /// エラーに、エラーが発生するまでに読み込んだ Token の数と、
+    /// エラーになった Token の位置 (Token が尽きた場合は最後の Token の位置) を設定する
+    fn locate(&self, mut error: ParserError) -> ParserError {
+        error.consumed = self.index;
+        for segment in error.segments.drain(..).rev() {
+            error.path.push('/');
+            error.path.push_str(&segment);
+        }
+        error.offset = self
+            .spans
+            .get(self.last)
+            .or_else(|| self.spans.last())
+            .map(|span| span.start);
+        error
     }
 
     /// Array の Parse
@@ -39,7 +187,10 @@ impl Parser {
         // 捨てる
         self.next_expect()?;
 
-        let mut array = vec![];
+        let mut array = Vec::with_capacity(self.count_array_elements());
+        if self.stop_partial() {
+            return Ok(Value::Array(array));
+        }
 
         // ] なら空配列を返す
         let token = self.peek_expect()?;
@@ -50,9 +201,16 @@ impl Parser {
         }
 
         loop {
+            self.collect_numbers(&mut array)?;
+
             // 残りの Value をパース
-            let value = self.parse()?;
+            let value = self
+                .parse_value()
+                .map_err(|e| e.with_parent(&array.len().to_string()))?;
             array.push(value);
+            if self.stop_partial() {
+                return Ok(Value::Array(array));
+            }
 
             // Array が終端もしくは次の要素があるかを確認
             let token = self.next_expect()?;
@@ -63,6 +221,12 @@ impl Parser {
                 }
                 // , なら次の要素をパース
                 Token::Comma => {
+                    if self.stop_partial() {
+                        return Ok(Value::Array(array));
+                    }
+                    if self.skip_trailing_comma(Token::RightBracket) {
+                        return Ok(Value::Array(array));
+                    }
                     continue;
                 }
                 // それ以外はエラー
@@ -76,6 +240,68 @@ impl Parser {
         }
     }
 
+    /// Array の中で 数値 , 数値 ... と続く部分を、parse を経由せずにまとめて読み込む。
+    /// 次の要素も数値である , までを読み込み、最後の数値は通常の処理に任せる
+    fn collect_numbers(&mut self, array: &mut Vec<Value>) -> Result<(), ParserError> {
+        while self.index + 2 < self.limit && self.fill(self.index + 2) {
+            let n = match &self.tokens[self.index..self.index + 3] {
+                [Token::Number(n), Token::Comma, Token::Number(_)] => *n,
+                _ => break,
+            };
+            self.allocate(std::mem::size_of::<Value>())
+                .map_err(|e| e.with_parent(&array.len().to_string()))?;
+            array.push(Value::Number(n));
+            self.index += 2;
+        }
+        Ok(())
+    }
+
+    /// 現在の Array の終端 ] までに含まれる要素数を数える (要素の先頭の Token を数える)。
+    /// ネストした Array/Object の中は数えない。確保しすぎないように、先読みは ARRAY_SCAN_LIMIT 個の
+    /// Token までにする。それより長い Array は数えた分だけ確保し、残りは Vec の伸長に任せる
+    fn count_array_elements(&self) -> usize {
+        let end = self
+            .limit
+            .min(self.index.saturating_add(ARRAY_SCAN_LIMIT))
+            .min(self.tokens.len());
+        let mut depth = 0usize;
+        let mut count = 0;
+        for token in &self.tokens[self.index..end] {
+            match token {
+                Token::RightBracket | Token::RightBrace if depth == 0 => break,
+                Token::RightBracket | Token::RightBrace => depth -= 1,
+                Token::Comma | Token::Colon | Token::WhiteSpace => {}
+                _ => {
+                    if depth == 0 {
+                        count += 1;
+                    }
+                    if matches!(token, Token::LeftBracket | Token::LeftBrace) {
+                        depth += 1;
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// allow_trailing_commas の場合に、直前の , の次が閉じ括弧 close なら読み飛ばして true を返す
+    fn skip_trailing_comma(&mut self, close: Token) -> bool {
+        if !self.options.allow_trailing_commas || self.peek() != Some(&close) {
+            return false;
+        }
+        if let Some(span) = self.spans.get(self.index - 1) {
+            self.warnings
+                .push(("accepted trailing comma".to_string(), span.start));
+        }
+        self.index += 1;
+        true
+    }
+
+    /// 寛容な設定で受け入れた記述の説明と、その位置 (バイト単位) を取り出す
+    pub(crate) fn take_warnings(&mut self) -> Vec<(String, usize)> {
+        std::mem::take(&mut self.warnings)
+    }
+
     /// Object の Parse
     /// {
     ///   "key1": 123,
@@ -94,6 +320,9 @@ impl Parser {
         self.next_expect()?;
 
         let mut object = std::collections::BTreeMap::new();
+        if self.stop_partial() {
+            return Ok(Value::Object(object));
+        }
 
         // } なら空の Object を返す
         if *self.peek_expect()? == Token::RightBrace {
@@ -105,12 +334,29 @@ impl Parser {
         loop {
             // ２文字分 (key, comma) 読み出す
             let token1 = self.next_expect()?.clone();
+            if self.stop_partial() {
+                return Ok(Value::Object(object));
+            }
             let token2 = self.next_expect()?;
 
             match (token1, token2) {
                 // String(key) と Colon
                 (Token::String(key), Token::Colon) => {
-                    object.insert(key, self.parse()?);
+                    if let Some(max) = self.options.max_key_len.filter(|max| key.len() > *max) {
+                        return Err(ParserError::new(&format!(
+                            "error: the length of a key exceeds the limit {}",
+                            max
+                        )));
+                    }
+                    self.allocate(key.len())?;
+                    if self.stop_partial() {
+                        return Ok(Value::Object(object));
+                    }
+                    let value = self.parse_value().map_err(|e| e.with_parent(&key))?;
+                    object.insert(key, value);
+                    if self.stop_partial() {
+                        return Ok(Value::Object(object));
+                    }
                 }
                 // それ以外はエラー
                 _ => {
@@ -126,6 +372,12 @@ impl Parser {
                     return Ok(Value::Object(object));
                 }
                 Token::Comma => {
+                    if self.stop_partial() {
+                        return Ok(Value::Object(object));
+                    }
+                    if self.skip_trailing_comma(Token::RightBrace) {
+                        return Ok(Value::Object(object));
+                    }
                     continue;
                 }
                 _ => {
@@ -139,13 +391,39 @@ impl Parser {
     }
 
     /// Token を評価して Value に変換する。
-    /// この関数は再帰的に呼び出される
+    /// エラーの場合は、それまでに読み込んだ Token の数とエラーになった位置を設定する
     pub fn parse(&mut self) -> Result<Value, ParserError> {
+        self.root = self.index;
+        self.parse_value().map_err(|e| self.locate(e))
+    }
+
+    /// Token を評価して Value に変換する。
+    /// この関数は再帰的に呼び出される
+    fn parse_value(&mut self) -> Result<Value, ParserError> {
         let token = self.peek_expect()?.clone();
+        let string_len = match &token {
+            Token::String(s) => s.len(),
+            _ => 0,
+        };
+        self.allocate(std::mem::size_of::<Value>() + string_len)?;
 
         match token {
             Token::LeftBrace => self.parse_object(),
             Token::LeftBracket => self.parse_array(),
+            // トップレベルに Object/Array 以外を許可しない
+            Token::String(_) | Token::Number(_) | Token::Bool(_) | Token::Null
+                if self.is_forbidden_root() =>
+            {
+                Err(ParserError::new(&format!(
+                    "error: the root value must be an object or array {:?}",
+                    token
+                )))
+            }
+            #[cfg(feature = "decimal")]
+            Token::Decimal(_) if self.is_forbidden_root() => Err(ParserError::new(&format!(
+                "error: the root value must be an object or array {:?}",
+                token
+            ))),
             Token::String(s) => {
                 self.next_expect()?;
                 Ok(Value::String(s))
@@ -154,6 +432,11 @@ impl Parser {
                 self.next_expect()?;
                 Ok(Value::Number(n))
             }
+            #[cfg(feature = "decimal")]
+            Token::Decimal(n) => {
+                self.next_expect()?;
+                Ok(Value::Decimal(n))
+            }
             Token::Bool(b) => {
                 self.next_expect()?;
                 Ok(Value::Bool(b))
@@ -162,28 +445,266 @@ impl Parser {
                 self.next_expect()?;
                 Ok(Value::Null)
             }
-            _ => {
-                return Err(ParserError::new(&format!(
-                    "error: a token must start {{ or [ or string or number or bool or null {:?}",
-                    token
-                )))
+            // 先頭から閉じ括弧や区切り文字が来ることはない
+            Token::RightBrace | Token::RightBracket if self.index == self.root => Err(
+                ParserError::new("error: unexpected closing bracket/brace at top level"),
+            ),
+            Token::Comma | Token::Colon if self.index == self.root => Err(ParserError::new(
+                &format!("error: unexpected token {:?} at top level", token),
+            )),
+            _ => Err(ParserError::new(&format!(
+                "error: a token must start {{ or [ or string or number or bool or null {:?}",
+                token
+            ))),
+        }
+    }
+
+    /// 組み立てる Value の大きさを数え、max_total_bytes を超えた場合はエラーを返す
+    fn allocate(&mut self, bytes: usize) -> Result<(), ParserError> {
+        self.allocated = self.allocated.saturating_add(bytes);
+        match self.options.max_total_bytes {
+            Some(max) if self.allocated > max => Err(ParserError::new(&format!(
+                "error: the total size of values exceeds the limit {} bytes",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// トップレベルでスカラー値が許可されていない位置か
+    fn is_forbidden_root(&self) -> bool {
+        self.index == self.root && self.options.require_root_container
+    }
+
+    /// budget 個の Token を読み込むまで Parse し、Parse できた部分の Value を返す。
+    /// 途中で止まった場合は resume_at に止まった位置を返し、Parser は呼び出す前の位置に戻る。
+    /// 途中から再開はしないので、budget を増やして再度呼び出すと最初から Parse し直してより大きな Value を返す
+    pub fn parse_with_budget(&mut self, budget: usize) -> Result<PartialValue, ParserError> {
+        let start = self.index;
+        let (allocated, warnings) = (self.allocated, self.warnings.len());
+        self.limit = start.saturating_add(budget);
+        self.partial = true;
+        self.truncated = false;
+
+        let result = if self.peek().is_some() {
+            self.parse().map(Some)
+        } else {
+            self.truncated = true;
+            Ok(None)
+        };
+        let resume_at = if self.truncated {
+            Some(self.index)
+        } else {
+            None
+        };
+        if resume_at.is_some() {
+            self.index = start;
+            self.allocated = allocated;
+            self.warnings.truncate(warnings);
+        }
+
+        self.limit = usize::MAX;
+        self.partial = false;
+        self.truncated = false;
+        Ok(PartialValue {
+            value: result?,
+            resume_at,
+        })
+    }
+
+    /// partial の場合に Token が尽きたかを確認する。尽きた場合は Parse を打ち切る
+    fn stop_partial(&mut self) -> bool {
+        if self.partial && (self.truncated || self.peek().is_none()) {
+            self.truncated = true;
+        }
+        self.truncated
+    }
+
+    /// Parser を消費して Value に変換する。
+    /// Value の後ろに Token が残っている場合はエラーを返す
+    pub fn into_value(mut self) -> Result<Value, ParserError> {
+        let value = self.parse()?;
+        self.expect_end()?;
+        Ok(value)
+    }
+
+    /// Token が残っていないことを確かめる。残っている場合はエラーを返す
+    pub(crate) fn expect_end(&mut self) -> Result<(), ParserError> {
+        match self.peek() {
+            Some(token) => {
+                let error =
+                    ParserError::new(&format!("error: unexpected trailing token {:?}", token));
+                Err(self.locate(error))
             }
+            None => Ok(()),
         }
     }
 
+    /// 次の Value を組み立てずに読み飛ばす。
+    /// 括弧の対応だけを確かめるので、Array/Object の中身の区切り文字の誤りは検出しない
+    pub fn skip_value(&mut self) -> Result<(), ParserError> {
+        let mut depth = 0usize;
+        loop {
+            match self.next_expect()? {
+                Token::LeftBrace | Token::LeftBracket => depth += 1,
+                Token::RightBrace | Token::RightBracket => {
+                    depth = depth.checked_sub(1).ok_or_else(|| {
+                        ParserError::new("error: unexpected closing bracket/brace at top level")
+                    })?;
+                }
+                token @ (Token::Comma | Token::Colon) if depth == 0 => {
+                    return Err(ParserError::new(&format!(
+                        "error: a token must start a value {:?}",
+                        token
+                    )))
+                }
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// 先頭の Object から key に対応する Value を探して返す。
+    /// 一致しないキーの Value は組み立てずに読み飛ばす。
+    /// 見つかった場合は、その Value の直後 (Object の残りの手前) で止まる
+    pub fn find_key(&mut self, key: &str) -> Result<Option<Value>, ParserError> {
+        if self.seek_key(key)? {
+            self.parse().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// 先頭の Object から key を探し、見つかった場合は対応する Value の手前まで読み進めて true を返す。
+    /// 見つからない場合は Object の終わりまで読み進めて false を返す
+    pub(crate) fn seek_key(&mut self, key: &str) -> Result<bool, ParserError> {
+        self.read_to_key(key).map_err(|e| self.locate(e))
+    }
+
+    /// seek_key の本体。エラーの位置は seek_key で設定する
+    fn read_to_key(&mut self, key: &str) -> Result<bool, ParserError> {
+        self.expect_token(Token::LeftBrace)?;
+        if self.peek_expect()? == &Token::RightBrace {
+            self.next_expect()?;
+            return Ok(false);
+        }
+
+        loop {
+            let found = match self.next_expect()? {
+                Token::String(k) => k == key,
+                token => {
+                    return Err(ParserError::new(&format!(
+                        "error: a key must be a string {:?}",
+                        token
+                    )))
+                }
+            };
+            self.expect_token(Token::Colon)?;
+            if found {
+                return Ok(true);
+            }
+            self.skip_value()?;
+            match self.next_expect()? {
+                Token::Comma => {}
+                Token::RightBrace => return Ok(false),
+                token => {
+                    return Err(ParserError::new(&format!(
+                        "error: a token must be , or }} {:?}",
+                        token
+                    )))
+                }
+            }
+        }
+    }
+
+    /// 先頭の Array の index 番目の要素を探し、見つかった場合はその要素の手前まで読み進めて true を返す。
+    /// 見つからない場合は Array の終わりまで読み進めて false を返す
+    pub(crate) fn seek_index(&mut self, index: usize) -> Result<bool, ParserError> {
+        self.read_to_index(index).map_err(|e| self.locate(e))
+    }
+
+    /// seek_index の本体。エラーの位置は seek_index で設定する
+    fn read_to_index(&mut self, index: usize) -> Result<bool, ParserError> {
+        self.expect_token(Token::LeftBracket)?;
+        if self.peek_expect()? == &Token::RightBracket {
+            self.next_expect()?;
+            return Ok(false);
+        }
+
+        for _ in 0..index {
+            self.skip_value()?;
+            match self.next_expect()? {
+                Token::Comma => {}
+                Token::RightBracket => return Ok(false),
+                token => {
+                    return Err(ParserError::new(&format!(
+                        "error: a token must be , or ] {:?}",
+                        token
+                    )))
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// 先頭の Token が expected であることを確かめて、１トークン進める
+    fn expect_token(&mut self, expected: Token) -> Result<(), ParserError> {
+        match self.next_expect()? {
+            token if *token == expected => Ok(()),
+            token => Err(ParserError::new(&format!(
+                "error: a token must be {:?} {:?}",
+                expected, token
+            ))),
+        }
+    }
+
+    /// index 番目の Token が読み込み済みであることを確かめる。
+    /// lexer がある場合は足りない分を読み込み、それでも無い場合は false を返す
+    fn fill(&mut self, index: usize) -> bool {
+        while self.tokens.len() <= index {
+            let Some(lexer) = &mut self.lexer else {
+                return false;
+            };
+            match lexer.next_spanned_token() {
+                Ok(Some((Token::WhiteSpace, _))) => {}
+                Ok(Some((token, span))) => {
+                    self.tokens.push(token);
+                    self.spans.push(span);
+                }
+                Ok(None) => self.lexer = None,
+                Err(e) => {
+                    self.lexer_error = Some(e);
+                    self.lexer = None;
+                }
+            }
+        }
+        true
+    }
+
     /// 先頭の Token を返す
-    fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.index)
+    pub(crate) fn peek(&mut self) -> Option<&Token> {
+        self.last = self.index;
+        if self.index < self.limit && self.fill(self.index) {
+            self.tokens.get(self.index)
+        } else {
+            None
+        }
     }
 
     /// 先頭のTokenを返す (先頭に Token があることを想定)
-    fn peek_expect(&self) -> Result<&Token, ParserError> {
+    fn peek_expect(&mut self) -> Result<&Token, ParserError> {
         self.peek()
             .ok_or_else(|| ParserError::new("error: a token isn't peekable"))
     }
 
     /// 先頭の Token を返して、１トークン進める
     fn next(&mut self) -> Option<&Token> {
+        self.last = self.index;
+        if self.index >= self.limit || !self.fill(self.index) {
+            return None;
+        }
         self.index += 1;
         self.tokens.get(self.index - 1)
     }
@@ -197,7 +718,7 @@ impl Parser {
 
 #[cfg(test)]
 mod tests {
-    use super::Parser;
+    use super::{Parser, ParserOptions};
     use crate::{lexer::Lexer, Value};
     use std::collections::BTreeMap;
 
@@ -271,4 +792,614 @@ mod tests {
         let array = Value::Array(vec![Value::Object(object)]);
         assert_eq!(value, array);
     }
+
+    #[test]
+    fn test_into_value() {
+        let json = r#"[1, "value"]"#;
+        let value = Parser::new(Lexer::new(json).tokenize().unwrap())
+            .into_value()
+            .unwrap();
+        assert_eq!(
+            value,
+            Value::Array(vec![Value::Number(1.0), Value::String("value".to_string())])
+        );
+
+        let json = r#"{"key": null} true"#;
+        let result = Parser::new(Lexer::new(json).tokenize().unwrap()).into_value();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_stray_token_at_top_level() {
+        let closing = "error: unexpected closing bracket/brace at top level";
+        [
+            ("}", closing),
+            ("]", closing),
+            ("] 1", closing),
+            (",", "error: unexpected token Comma at top level"),
+            (":", "error: unexpected token Colon at top level"),
+        ]
+        .into_iter()
+        .for_each(|(json, msg)| {
+            let err = Parser::new(Lexer::new(json).tokenize().unwrap())
+                .parse()
+                .unwrap_err();
+            assert_eq!(err.msg, msg, "{}", json);
+        });
+
+        // 2 つ目の Value もトップレベルとして扱う
+        let mut parser = Parser::new(Lexer::new("[1] ]").tokenize().unwrap());
+        parser.parse().unwrap();
+        assert_eq!(parser.parse().unwrap_err().msg, closing);
+
+        let mut parser = Parser::new(Lexer::new("{}").tokenize().unwrap());
+        parser.next_expect().unwrap();
+        assert_eq!(parser.skip_value().unwrap_err().msg, closing);
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_parse_decimal() {
+        use crate::lexer::LexerOptions;
+        use rust_decimal::Decimal;
+        use std::str::FromStr;
+
+        let options = LexerOptions {
+            decimal_numbers: true,
+            ..LexerOptions::default()
+        };
+        let json = r#"{"price": 19.99, "rate": 0.000000000000000000123}"#;
+        let value = Parser::new(Lexer::with_options(json, options).tokenize().unwrap())
+            .parse()
+            .unwrap();
+        assert_eq!(
+            value["price"],
+            Value::Decimal(Decimal::from_str("19.99").unwrap())
+        );
+        match &value["rate"] {
+            Value::Decimal(d) => assert_eq!(d.to_string(), "0.000000000000000000123"),
+            v => panic!("a decimal value is expected {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_parse_with_budget() {
+        let json = r#"[1, 2, 3, {"a": 4, "b": 5}]"#;
+        let tokens = Lexer::new(json).tokenize().unwrap();
+        let len = tokens.len();
+
+        // [ 1 , 2 , まで
+        let partial = Parser::new(tokens.clone()).parse_with_budget(5).unwrap();
+        assert_eq!(
+            partial.value,
+            Some(Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]))
+        );
+        assert_eq!(partial.resume_at, Some(5));
+
+        // {"a": 4, "b" まで。値の無いキーは含まない
+        let partial = Parser::new(tokens.clone()).parse_with_budget(12).unwrap();
+        let mut object = BTreeMap::new();
+        object.insert("a".to_string(), Value::Number(4.0));
+        assert_eq!(
+            partial.value,
+            Some(Value::Array(vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Number(3.0),
+                Value::Object(object),
+            ]))
+        );
+        assert_eq!(partial.resume_at, Some(12));
+
+        let partial = Parser::new(tokens.clone()).parse_with_budget(0).unwrap();
+        assert_eq!(partial.value, None);
+        assert_eq!(partial.resume_at, Some(0));
+
+        let partial = Parser::new(tokens.clone()).parse_with_budget(len).unwrap();
+        assert_eq!(partial.value, Some(Parser::new(tokens).parse().unwrap()));
+        assert_eq!(partial.resume_at, None);
+
+        // 途中で止まった後に budget を増やして呼び出すと、最初から Parse し直す
+        let json = r#"{"a": 1, "b": 2}"#;
+        let mut parser = Parser::new(Lexer::new(json).tokenize().unwrap());
+        let partial = parser.parse_with_budget(4).unwrap();
+        assert_eq!(partial.value, Some(crate::parse(r#"{"a": 1}"#).unwrap()));
+        assert_eq!(partial.resume_at, Some(4));
+        let partial = parser.parse_with_budget(100).unwrap();
+        assert_eq!(partial.value, Some(crate::parse(json).unwrap()));
+        assert_eq!(partial.resume_at, None);
+        assert_eq!(parser.peek(), None);
+    }
+
+    #[test]
+    fn test_count_array_elements() {
+        let cases = [
+            ("[]", 0),
+            ("[1]", 1),
+            ("[1, [2, 3], {\"a\": 4, \"b\": 5}]", 3),
+            ("[1, 2", 2),
+            // 値のない , は数えない
+            ("[,,,,]", 0),
+        ];
+        cases.into_iter().for_each(|(json, expected)| {
+            let mut parser = Parser::new(Lexer::new(json).tokenize().unwrap());
+            parser.next_expect().unwrap();
+            assert_eq!(parser.count_array_elements(), expected, "{}", json);
+        });
+    }
+
+    #[test]
+    fn test_array_capacity() {
+        let parse = |json: &str| Parser::new(Lexer::new(json).tokenize().unwrap()).parse();
+        let capacity = |value: &Value| match value {
+            Value::Array(array) => array.capacity(),
+            _ => panic!("not an array: {:?}", value),
+        };
+
+        // 要素数分を一度に確保する
+        let numbers = (0..500).map(|i| i.to_string()).collect::<Vec<_>>();
+        let value = parse(&format!("[{}]", numbers.join(", "))).unwrap();
+        assert_eq!(capacity(&value), 500);
+
+        // ネストした Array はそれぞれの要素数で確保する
+        let value = parse(&format!("[{}]", vec!["[1, 2]"; 50].join(", "))).unwrap();
+        assert_eq!(capacity(&value), 50);
+        value.iter().for_each(|v| assert_eq!(capacity(v), 2));
+
+        // 値のない , は数えないので、その分を確保することはない
+        let json = format!("[{}]", ",".repeat(100_000));
+        let mut parser = Parser::new(Lexer::new(&json).tokenize().unwrap());
+        parser.next_expect().unwrap();
+        assert_eq!(parser.count_array_elements(), 0);
+
+        // 先読みの上限を超える Array は数えた分だけ確保して、残りは伸長する
+        let numbers = (0..5000).map(|i| i.to_string()).collect::<Vec<_>>();
+        let json = format!("[{}]", numbers.join(", "));
+        let mut parser = Parser::new(Lexer::new(&json).tokenize().unwrap());
+        parser.next_expect().unwrap();
+        assert!(parser.count_array_elements() < 5000);
+        assert_eq!(parse(&json).unwrap().get_array_len(), Some(5000));
+    }
+
+    #[test]
+    fn test_require_root_container() {
+        let value = Parser::new(Lexer::new("5").tokenize().unwrap())
+            .parse()
+            .unwrap();
+        assert_eq!(value, Value::Number(5.0));
+
+        let options = ParserOptions {
+            require_root_container: true,
+            ..ParserOptions::default()
+        };
+        let result =
+            Parser::with_options(Lexer::new("5").tokenize().unwrap(), options.clone()).parse();
+        assert!(result.is_err());
+
+        let value = Parser::with_options(Lexer::new("[5]").tokenize().unwrap(), options.clone())
+            .parse()
+            .unwrap();
+        assert_eq!(value, Value::Array(vec![Value::Number(5.0)]));
+
+        // 2 回目の parse や、parse_with_budget で巻き戻した後もトップレベルとして扱う
+        let mut parser =
+            Parser::with_options(Lexer::new("[1] 2").tokenize().unwrap(), options.clone());
+        parser.parse().unwrap();
+        assert!(parser.parse().is_err());
+
+        let mut parser = Parser::with_options(Lexer::new("[1] 2").tokenize().unwrap(), options);
+        parser.parse().unwrap();
+        assert!(parser.parse_with_budget(0).unwrap().resume_at.is_some());
+        assert!(parser.parse_with_budget(1).is_err());
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_partial() {
+        let (value, truncated) =
+            crate::parse_partial(r#"{"a": 1, "b": [true, {"c": null}, "unterminated"#).unwrap();
+        let mut nested = BTreeMap::new();
+        nested.insert("c".to_string(), Value::Null);
+        let mut object = BTreeMap::new();
+        object.insert("a".to_string(), Value::Number(1.0));
+        object.insert(
+            "b".to_string(),
+            Value::Array(vec![Value::Bool(true), Value::Object(nested)]),
+        );
+        assert_eq!(value, Value::Object(object));
+        assert!(truncated);
+
+        let (value, truncated) = crate::parse_partial(r#"{"a": [1, 2]}"#).unwrap();
+        assert_eq!(
+            value,
+            Parser::new(Lexer::new(r#"{"a": [1, 2]}"#).tokenize().unwrap())
+                .parse()
+                .unwrap()
+        );
+        assert!(!truncated);
+
+        assert!(crate::parse_partial(r#"{"a" 1, "b"#).is_err());
+        // 途切れた位置の直前の , や : の抜けは、途切れたのではなく誤り
+        for json in [
+            "[1 2",
+            r#"["x" 5"#,
+            r#"{"a": 1 "b"#,
+            r#"{"a" 1"#,
+            r#"{"a" "b"#,
+            r#"{"a": 1 tr"#,
+        ] {
+            assert!(crate::parse_partial(json).is_err(), "{}", json);
+        }
+        // 途切れた Token の前までが正しければ Parse できる
+        for (json, expected) in [
+            ("[1, 2", "[1]"),
+            (r#"{"a": tr"#, "{}"),
+            (r#"{"a": 1, "b"#, r#"{"a": 1}"#),
+            (r#"["x", "y"#, r#"["x"]"#),
+        ] {
+            let (value, truncated) = crate::parse_partial(json).unwrap();
+            assert_eq!(value, crate::parse(expected).unwrap(), "{}", json);
+            assert!(truncated);
+        }
+        assert!(crate::parse_partial("").is_err());
+
+        let err = crate::parse_partial("[1,2] 3").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error at line 1, column 7: unexpected trailing token Number(3.0)"
+        );
+        let err = crate::parse_partial("{} {").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error at line 1, column 4: unexpected trailing token LeftBrace"
+        );
+    }
+
+    #[test]
+    fn test_flat_number_array() {
+        let parse = |json: &str| Parser::new(Lexer::new(json).tokenize().unwrap()).into_value();
+
+        let json = format!(
+            "[{}]",
+            (0..1000)
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let value = parse(&json).unwrap();
+        assert_eq!(
+            value,
+            Value::Array((0..1000).map(|i| Value::Number(i as f64)).collect())
+        );
+
+        // 数値以外が混ざっていても同じ結果になる
+        assert_eq!(
+            parse(r#"[1, 2, "a", 3, 4, [5, 6], 7]"#).unwrap(),
+            Value::Array(vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::String("a".to_string()),
+                Value::Number(3.0),
+                Value::Number(4.0),
+                Value::Array(vec![Value::Number(5.0), Value::Number(6.0)]),
+                Value::Number(7.0),
+            ])
+        );
+        assert!(parse("[1, 2, 3,]").is_err());
+        assert!(parse("[1, 2, 3").is_err());
+        assert!(parse("[1, 2 3]").is_err());
+
+        let options = ParserOptions {
+            max_total_bytes: Some(std::mem::size_of::<Value>() * 3),
+            ..ParserOptions::default()
+        };
+        let tokens = Lexer::new("[1, 2, 3, 4]").tokenize().unwrap();
+        let error = Parser::with_options(tokens, options).parse().unwrap_err();
+        assert_eq!(error.path, "/2");
+    }
+
+    #[test]
+    fn test_empty_key() {
+        let json = r#"{"": 1, "a": {"": ""}}"#;
+        let value = Parser::new(Lexer::new(json).tokenize().unwrap())
+            .into_value()
+            .unwrap();
+        assert_eq!(value.get(""), Some(&Value::Number(1.0)));
+        assert_eq!(value["a"].get(""), Some(&Value::String("".to_string())));
+        assert_eq!(value.pointer("/a/"), Some(&Value::String("".to_string())));
+        assert_eq!(value.to_json_string(), r#"{"":1,"a":{"":""}}"#);
+        assert_eq!(crate::parse(&value.to_json_string()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_max_total_bytes() {
+        let value_size = std::mem::size_of::<Value>();
+        let json = r#"{"ab": ["cd", null]}"#;
+        // Value 4 つと、"ab" と "cd" の 4 バイト
+        let total = value_size * 4 + 4;
+
+        let options = ParserOptions {
+            max_total_bytes: Some(total),
+            ..ParserOptions::default()
+        };
+        let parser = Parser::with_options(Lexer::new(json).tokenize().unwrap(), options);
+        assert!(parser.into_value().is_ok());
+
+        let options = ParserOptions {
+            max_total_bytes: Some(total - 1),
+            ..ParserOptions::default()
+        };
+        let parser = Parser::with_options(Lexer::new(json).tokenize().unwrap(), options);
+        let error = parser.into_value().unwrap_err();
+        assert_eq!(
+            error.msg,
+            format!(
+                "error: the total size of values exceeds the limit {} bytes",
+                total - 1
+            )
+        );
+        assert_eq!(error.path, "/ab/1");
+
+        // 大きな文字列 1 つでも上限を超える
+        let json = format!("[\"{}\"]", "x".repeat(10_000));
+        let options = ParserOptions {
+            max_total_bytes: Some(1024),
+            ..ParserOptions::default()
+        };
+        let parser = Parser::with_options(Lexer::new(&json).tokenize().unwrap(), options);
+        assert!(parser.into_value().is_err());
+    }
+
+    #[test]
+    fn test_max_key_len() {
+        let options = ParserOptions {
+            max_key_len: Some(4),
+            ..ParserOptions::default()
+        };
+        let json = r#"{"abcd": {"efgh": 1}}"#;
+        let parser = Parser::with_options(Lexer::new(json).tokenize().unwrap(), options.clone());
+        assert!(parser.into_value().is_ok());
+
+        let json = format!(r#"{{"a": {{"{}": 1}}}}"#, "k".repeat(1000));
+        let error = Parser::with_options(Lexer::new(&json).tokenize().unwrap(), options)
+            .parse()
+            .unwrap_err();
+        assert_eq!(error.msg, "error: the length of a key exceeds the limit 4");
+        assert_eq!(error.path, "/a");
+
+        // 設定しない場合は制限しない
+        assert!(Parser::new(Lexer::new(&json).tokenize().unwrap())
+            .parse()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let json = r#"{"a": [1, 2,], "b": {"c": null,},}"#;
+        let result = Parser::new(Lexer::new(json).tokenize().unwrap()).parse();
+        assert!(result.is_err());
+
+        let options = ParserOptions {
+            allow_trailing_commas: true,
+            ..ParserOptions::default()
+        };
+        let value = Parser::with_options(Lexer::new(json).tokenize().unwrap(), options.clone())
+            .parse()
+            .unwrap();
+        assert_eq!(
+            value,
+            Parser::new(
+                Lexer::new(r#"{"a": [1, 2], "b": {"c": null}}"#)
+                    .tokenize()
+                    .unwrap()
+            )
+            .parse()
+            .unwrap()
+        );
+
+        // 要素の無い , は許可しない
+        let result = Parser::with_options(Lexer::new("[,]").tokenize().unwrap(), options).parse();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_with_warnings() {
+        use crate::{lexer::LexerOptions, Warning};
+
+        let json = "{\n  // comment\n  \"a\": [1, 2,],\n  \"b\": 3, /* block */\n}";
+        let lexer_options = LexerOptions {
+            allow_comments: true,
+            ..LexerOptions::default()
+        };
+        let parser_options = ParserOptions {
+            allow_trailing_commas: true,
+            ..ParserOptions::default()
+        };
+        let (value, warnings) =
+            crate::parse_with_warnings(json, lexer_options, parser_options).unwrap();
+        assert_eq!(
+            value,
+            Parser::new(Lexer::new(r#"{"a": [1, 2], "b": 3}"#).tokenize().unwrap())
+                .parse()
+                .unwrap()
+        );
+
+        let warning = |msg: &str, line, column| Warning {
+            msg: msg.to_string(),
+            line,
+            column,
+        };
+        assert_eq!(
+            warnings,
+            vec![
+                warning("accepted comment", 2, 3),
+                warning("accepted trailing comma", 3, 13),
+                warning("accepted trailing comma", 4, 9),
+                warning("accepted comment", 4, 11),
+            ]
+        );
+
+        // ルートの Value の後ろの Token はエラー
+        let err =
+            crate::parse_with_warnings("[1] 2", LexerOptions::default(), ParserOptions::default())
+                .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error at line 1, column 5: unexpected trailing token Number(2.0)"
+        );
+    }
+
+    #[test]
+    fn test_parse_with_warnings_per_leniency() {
+        use crate::lexer::LexerOptions;
+
+        let options = LexerOptions {
+            allow_python_literals: true,
+            allow_number_underscores: true,
+            allow_bare_words: true,
+            ..LexerOptions::default()
+        };
+        [
+            ("[+1]", "accepted leading + in a number"),
+            ("[.5]", "accepted number without a digit before ."),
+            ("[-.5]", "accepted number without a digit before ."),
+            ("[5.]", "accepted number without a digit after ."),
+            ("[007]", "accepted leading zeros in a number"),
+            ("[1_000]", "accepted _ in a number"),
+            ("[True]", "accepted Python literal True"),
+            ("[None]", "accepted Python literal None"),
+            ("[ok]", "accepted bare word ok"),
+        ]
+        .into_iter()
+        .for_each(|(json, expected)| {
+            let (_, warnings) =
+                crate::parse_with_warnings(json, options.clone(), ParserOptions::default())
+                    .unwrap();
+            let warnings: Vec<_> = warnings.iter().map(|w| w.msg.as_str()).collect();
+            assert_eq!(warnings, vec![expected], "{}", json);
+        });
+
+        // allow_bare_words が無い場合も Python の True/False/None を警告する
+        let python_only = LexerOptions {
+            allow_python_literals: true,
+            ..LexerOptions::default()
+        };
+        let (_, warnings) =
+            crate::parse_with_warnings("[False]", python_only, ParserOptions::default()).unwrap();
+        assert_eq!(warnings[0].msg, "accepted Python literal False");
+
+        // 標準の JSON の記述では警告しない
+        let (_, warnings) = crate::parse_with_warnings(
+            r#"[0, -0.5, 1e5, 10, true, null, "s"]"#,
+            options,
+            ParserOptions::default(),
+        )
+        .unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_error_path() {
+        let json = r#"{"users": [{"name": "a"}, {"name": "b"}, {"name": }]}"#;
+        let err = Parser::new(Lexer::new(json).tokenize().unwrap())
+            .parse()
+            .unwrap_err();
+        assert_eq!(err.path, "/users/2/name");
+
+        let json = r#"{"a/b": [1, 2 3]}"#;
+        let err = Parser::new(Lexer::new(json).tokenize().unwrap())
+            .parse()
+            .unwrap_err();
+        assert_eq!(err.path, "/a~1b");
+
+        let err = Parser::new(Lexer::new("[").tokenize().unwrap())
+            .parse()
+            .unwrap_err();
+        assert_eq!(err.path, "");
+    }
+
+    #[test]
+    fn test_skip_value() {
+        let json = r#"[{"a": [1, {"b": [[]]}], "c": "]"}, 2] true"#;
+        let mut parser = Parser::new(Lexer::new(json).tokenize().unwrap());
+        parser.skip_value().unwrap();
+        assert_eq!(parser.parse().unwrap(), Value::Bool(true));
+
+        let json = r#"{"x": {"y": [1, 2]}, "z": 3}"#;
+        let mut parser = Parser::new(Lexer::new(json).tokenize().unwrap());
+        // { と "x" と : を読み進める
+        parser.next_expect().unwrap();
+        parser.next_expect().unwrap();
+        parser.next_expect().unwrap();
+        parser.skip_value().unwrap();
+        assert_eq!(parser.next_expect().unwrap(), &crate::Token::Comma);
+
+        for json in ["[1, [2]", "]", ","] {
+            let mut parser = Parser::new(Lexer::new(json).tokenize().unwrap());
+            assert!(parser.skip_value().is_err());
+        }
+    }
+
+    #[test]
+    fn test_find_key() {
+        let json = r#"{"a": {"target": 0}, "b": [1, [2, 3]], "target": {"id": 7}, "c": 1}"#;
+        let mut parser = Parser::new(Lexer::new(json).tokenize().unwrap());
+        let value = parser.find_key("target").unwrap().unwrap();
+        assert_eq!(value["id"], Value::Number(7.0));
+
+        let mut parser = Parser::new(Lexer::new(json).tokenize().unwrap());
+        assert_eq!(parser.find_key("missing").unwrap(), None);
+        assert_eq!(parser.peek(), None);
+
+        let mut parser = Parser::new(Lexer::new("[1]").tokenize().unwrap());
+        assert!(parser.find_key("a").is_err());
+    }
+
+    #[test]
+    fn test_consumed() {
+        let parse = |json: &str| Parser::new(Lexer::new(json).tokenize().unwrap()).into_value();
+
+        // [ 1 , 2 3 まで読み込んだところで失敗する
+        let error = parse("[1, 2 3]").unwrap_err();
+        assert_eq!(error.consumed, 5);
+
+        // { "a" : 1 , "b" 2 まで読み込んだところで失敗する
+        let error = parse(r#"{"a": 1, "b" 2}"#).unwrap_err();
+        assert_eq!(error.consumed, 7);
+        assert_eq!(error.path, "");
+
+        let error = parse("1 2").unwrap_err();
+        assert_eq!(error.consumed, 1);
+    }
+
+    #[test]
+    fn test_error_position() {
+        let parse = |json: &str| {
+            let tokens = Lexer::new(json).tokenize_with_offsets().unwrap();
+            Parser::with_spans(tokens, ParserOptions::default())
+                .into_value()
+                .map_err(|e| e.locate_in(json))
+        };
+
+        let error = parse("{\n  \"a\": 1,\n  \"b\" 2\n}").unwrap_err();
+        assert_eq!(error.offset, Some(18));
+        assert_eq!((error.line, error.column), (3, 7));
+
+        let error = parse("[\n  1,\n  :\n]").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 3));
+
+        let error = parse("[1]\n  2").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+
+        // Token が尽きた場合は最後の Token の位置
+        let error = parse("[\n  1,").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 4));
+
+        // 位置が分からない場合は None と 0
+        let error = Parser::new(Lexer::new("[1 2]").tokenize().unwrap())
+            .parse()
+            .unwrap_err();
+        assert_eq!(error.offset, None);
+        assert_eq!((error.line, error.column), (0, 0));
+    }
 }
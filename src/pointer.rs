@@ -0,0 +1,448 @@
+use crate::Value;
+
+/// JSON Pointer (RFC 6901) を参照トークンに分割する。
+/// ~1 は / に、~0 は ~ に戻す。空文字列は Value 全体を指す。
+/// / で始まらない文字列は JSON Pointer ではないので None を返す
+pub(crate) fn split_pointer(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(vec![]);
+    }
+    let rest = pointer.strip_prefix('/')?;
+    Some(
+        rest.split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
+/// Object のキーを JSON Pointer の参照トークンにエスケープする
+pub(crate) fn escape_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// 参照トークンを Array の添字として読む。先頭の 0 (e.g. "01") は許可しない
+pub(crate) fn parse_index(token: &str) -> Option<usize> {
+    if token.len() > 1 && token.starts_with('0') {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Value のすべてのノードを、その JSON Pointer と一緒に深さ優先 (親が先) で返すイテレータ。
+/// 次に返すノードをスタックに積んでおき、返す時にその子要素を積む。
+/// Raw は自身を返した後、Parse した Value の子要素をたどる
+struct Walk<'a> {
+    stack: Vec<(String, &'a Value)>,
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = (String, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, value) = self.stack.pop()?;
+        // 先頭の子要素から返すように、逆順に積む
+        match value.resolved() {
+            Value::Object(object) => self.stack.extend(
+                object
+                    .iter()
+                    .rev()
+                    .map(|(key, v)| (format!("{}/{}", path, escape_token(key)), v)),
+            ),
+            Value::Array(array) => self.stack.extend(
+                array
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(index, v)| (format!("{}/{}", path, index), v)),
+            ),
+            _ => {}
+        }
+        Some((path, value))
+    }
+}
+
+impl Value {
+    /// JSON Pointer (e.g. "/a/b/0") が指す Value を返す。存在しない場合は None を返す
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        split_pointer(pointer)?
+            .iter()
+            .try_fold(self, |value, token| match value.resolved() {
+                Value::Object(object) => object.get(token),
+                Value::Array(array) => array.get(parse_index(token)?),
+                _ => None,
+            })
+    }
+
+    /// JSON Pointer が指す Value を変更可能な参照で返す。存在しない場合は None を返す
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        split_pointer(pointer)?
+            .iter()
+            .try_fold(self, |value, token| match value.resolved_mut() {
+                Value::Object(object) => object.get_mut(token),
+                Value::Array(array) => array.get_mut(parse_index(token)?),
+                _ => None,
+            })
+    }
+
+    /// JSON Pointer が指す Value を取り除いて返す。存在しない場合は None を返す。
+    /// Array の要素を取り除くと、後ろの要素は前に詰められる。
+    /// 空文字列 (Value 全体) は取り除けないので None を返す
+    pub fn remove_path(&mut self, pointer: &str) -> Option<Value> {
+        let tokens = split_pointer(pointer)?;
+        let (last, parents) = tokens.split_last()?;
+        let parent = parents
+            .iter()
+            .try_fold(self, |value, token| match value.resolved_mut() {
+                Value::Object(object) => object.get_mut(token),
+                Value::Array(array) => array.get_mut(parse_index(token)?),
+                _ => None,
+            })?;
+        match parent.resolved_mut() {
+            Value::Object(object) => object.remove(last),
+            Value::Array(array) => {
+                let index = parse_index(last).filter(|index| *index < array.len())?;
+                Some(array.remove(index))
+            }
+            _ => None,
+        }
+    }
+
+    /// JSON Pointer が指す部分木を複製して返す。存在しない場合は None を返す。
+    /// 元の Value を手放した後も、一部だけを持ち続けたい場合に使う
+    pub fn clone_at(&self, pointer: &str) -> Option<Value> {
+        self.pointer(pointer).cloned()
+    }
+
+    /// pointers が指す Value とその祖先だけを残し、それ以外の要素を取り除く。
+    /// JSON Pointer として不正なものや、存在しない場所を指すものは無視する。
+    /// Array の要素を取り除くと、残った要素の添字は詰められる
+    pub fn retain_paths(&mut self, pointers: &[&str]) {
+        fn retain(value: &mut Value, paths: &[&[String]]) {
+            // 指されている Value は丸ごと残す
+            if paths.iter().any(|path| path.is_empty()) {
+                return;
+            }
+            // token に一致する path の残りを返す
+            let children = |matches: &dyn Fn(&str) -> bool| -> Vec<&[String]> {
+                paths
+                    .iter()
+                    .filter(|path| matches(&path[0]))
+                    .map(|path| &path[1..])
+                    .collect()
+            };
+            match value.resolved_mut() {
+                Value::Object(object) => object.retain(|key, v| {
+                    let rest = children(&|token| token == key);
+                    retain(v, &rest);
+                    !rest.is_empty()
+                }),
+                Value::Array(array) => {
+                    let mut index = 0;
+                    array.retain_mut(|v| {
+                        let rest = children(&|token| parse_index(token) == Some(index));
+                        index += 1;
+                        retain(v, &rest);
+                        !rest.is_empty()
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        // 存在しない場所を指すものは、途中の祖先も残さない
+        let paths: Vec<Vec<String>> = pointers
+            .iter()
+            .filter(|pointer| self.pointer(pointer).is_some())
+            .filter_map(|pointer| split_pointer(pointer))
+            .collect();
+        let paths: Vec<&[String]> = paths.iter().map(|path| path.as_slice()).collect();
+        retain(self, &paths);
+    }
+
+    /// 自身を含むすべてのノードを、その JSON Pointer と一緒に深さ優先 (親が先、Object はキーの順) で返す
+    fn walk(&self) -> Walk<'_> {
+        Walk {
+            stack: vec![(String::new(), self)],
+        }
+    }
+
+    /// f を満たすすべてのノードの JSON Pointer を、自身を含めて深さ優先 (親が先) の順で返す。
+    /// Raw は Parse した Value の子要素もたどる
+    pub fn paths_where<F: Fn(&Value) -> bool>(&self, f: F) -> Vec<String> {
+        self.walk()
+            .filter(|(_, value)| f(value))
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Array/Object 以外の末端の Value を、その JSON Pointer と一緒に深さ優先 (Object はキーの順) で返す。
+    /// Array/Object 自体は返さないので、空の Array/Object は何も返さない。
+    /// Raw は is_scalar と同じく Parse した Value で判定し、Array/Object の場合はその中の末端を返す
+    pub fn leaves(&self) -> impl Iterator<Item = (String, &Value)> {
+        self.walk().filter(|(_, value)| value.is_scalar())
+    }
+
+    /// ignore_pointers が指す場所を無視して other と比較する (e.g. タイムスタンプや ID を除いた API レスポンスの比較)。
+    /// 無視する場所は片方にしか存在しなくてもよい
+    pub fn eq_ignoring(&self, other: &Value, ignore_pointers: &[&str]) -> bool {
+        fn eq(a: Option<&Value>, b: Option<&Value>, path: &mut String, ignore: &[&str]) -> bool {
+            if ignore.contains(&path.as_str()) {
+                return true;
+            }
+            let (a, b) = match (a, b) {
+                (Some(a), Some(b)) => (a, b),
+                _ => return false,
+            };
+
+            let len = path.len();
+            let mut child = |token: &str, a: Option<&Value>, b: Option<&Value>| {
+                path.push('/');
+                path.push_str(token);
+                let result = eq(a, b, path, ignore);
+                path.truncate(len);
+                result
+            };
+            match (a.resolved(), b.resolved()) {
+                (Value::Object(a), Value::Object(b)) => a
+                    .keys()
+                    .chain(b.keys().filter(|key| !a.contains_key(*key)))
+                    .all(|key| child(&escape_token(key), a.get(key), b.get(key))),
+                (Value::Array(a), Value::Array(b)) => {
+                    (0..a.len().max(b.len())).all(|i| child(&i.to_string(), a.get(i), b.get(i)))
+                }
+                (a, b) => a == b,
+            }
+        }
+
+        eq(Some(self), Some(other), &mut String::new(), ignore_pointers)
+    }
+
+    /// JSON Pointer に一致するすべての Value を返す。
+    /// 標準の JSON Pointer の拡張として、* は Array のすべての要素、もしくは Object のすべての値に一致する。
+    /// (* というキーそのものを指したい場合は pointer を使う)
+    pub fn pointer_all(&self, pointer: &str) -> Vec<&Value> {
+        fn collect<'a>(value: &'a Value, tokens: &[String], result: &mut Vec<&'a Value>) {
+            let (token, rest) = match tokens.split_first() {
+                Some(split) => split,
+                None => {
+                    result.push(value);
+                    return;
+                }
+            };
+            if token == "*" {
+                value.iter().for_each(|v| collect(v, rest, result));
+                return;
+            }
+            let child = match value.resolved() {
+                Value::Object(object) => object.get(token),
+                Value::Array(array) => parse_index(token).and_then(|i| array.get(i)),
+                _ => None,
+            };
+            if let Some(child) = child {
+                collect(child, rest, result);
+            }
+        }
+
+        let mut result = vec![];
+        if let Some(tokens) = split_pointer(pointer) {
+            collect(self, &tokens, &mut result);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, Value};
+
+    #[test]
+    fn test_pointer() {
+        let value = parse(r#"{"a": {"b": [1, {"c/d": true, "e~f": null}]}}"#).unwrap();
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/a/b/0"), Some(&Value::Number(1.0)));
+        assert_eq!(value.pointer("/a/b/1/c~1d"), Some(&Value::Bool(true)));
+        assert_eq!(value.pointer("/a/b/1/e~0f"), Some(&Value::Null));
+        assert_eq!(value.pointer("/a/b/2"), None);
+        assert_eq!(value.pointer("/a/b/00"), None);
+        assert_eq!(value.pointer("a"), None);
+
+        let mut value = value;
+        *value.pointer_mut("/a/b/0").unwrap() = Value::Number(2.0);
+        assert_eq!(value.pointer("/a/b/0"), Some(&Value::Number(2.0)));
+    }
+
+    #[test]
+    fn test_pointer_all() {
+        let value = parse(r#"{"items": [{"id": 1}, {"id": 2}, {"name": "x"}], "meta": {"id": 3}}"#)
+            .unwrap();
+        assert_eq!(
+            value.pointer_all("/items/*/id"),
+            vec![&Value::Number(1.0), &Value::Number(2.0)]
+        );
+        assert_eq!(value.pointer_all("/*/id"), vec![&Value::Number(3.0)]);
+        assert_eq!(value.pointer_all("/meta/id"), vec![&Value::Number(3.0)]);
+        assert!(value.pointer_all("/items/*/missing").is_empty());
+        assert!(value.pointer_all("/missing/*").is_empty());
+    }
+
+    #[test]
+    fn test_clone_at() {
+        let mut value = parse(r#"{"a": {"b": {"c": [1, 2]}}, "d": null}"#).unwrap();
+        let mut subtree = value.clone_at("/a/b").unwrap();
+        assert_eq!(subtree, parse(r#"{"c": [1, 2]}"#).unwrap());
+
+        // 複製なので元の Value とは独立している
+        *subtree.pointer_mut("/c/0").unwrap() = Value::Null;
+        *value.pointer_mut("/a/b/c/1").unwrap() = Value::Bool(true);
+        assert_eq!(subtree, parse(r#"{"c": [null, 2]}"#).unwrap());
+        assert_eq!(value.pointer("/a/b/c"), Some(&parse("[1, true]").unwrap()));
+        drop(value);
+        assert_eq!(subtree.pointer("/c/1"), Some(&Value::Number(2.0)));
+
+        let value = parse(r#"{"a": 1}"#).unwrap();
+        assert_eq!(value.clone_at(""), Some(value.clone()));
+        assert_eq!(value.clone_at("/b"), None);
+    }
+
+    #[test]
+    fn test_retain_paths() {
+        let mut value = parse(
+            r#"{"a": {"b": {"x": 1}, "c": 2}, "c": [1, 2], "d": {"e": 3}, "f": [{"g": 1, "h": 2}, 5, {"g": 3}]}"#,
+        )
+        .unwrap();
+        value.retain_paths(&["/a/b", "/c", "/f/2/g", "/missing/x", "invalid"]);
+        assert_eq!(
+            value,
+            parse(r#"{"a": {"b": {"x": 1}}, "c": [1, 2], "f": [{"g": 3}]}"#).unwrap()
+        );
+
+        // 存在しない場所を指す場合は、存在する祖先も残さない
+        let mut value = parse(r#"{"a": {"b": {"x": 1}}, "c": 2}"#).unwrap();
+        value.retain_paths(&["/a/b/zzz"]);
+        assert_eq!(value, parse("{}").unwrap());
+        let mut value = parse(r#"{"a": {"b": {"x": 1}}, "c": 2}"#).unwrap();
+        value.retain_paths(&["/a/b/zzz", "/c"]);
+        assert_eq!(value, parse(r#"{"c": 2}"#).unwrap());
+
+        let mut value = parse(r#"{"a": 1}"#).unwrap();
+        value.retain_paths(&[]);
+        assert_eq!(value, parse("{}").unwrap());
+
+        let mut value = parse(r#"{"a": 1}"#).unwrap();
+        value.retain_paths(&[""]);
+        assert_eq!(value, parse(r#"{"a": 1}"#).unwrap());
+    }
+
+    #[test]
+    fn test_remove_path() {
+        let mut value = parse(r#"{"a": {"b": {"c": 1}, "d": 2}, "e": [10, 20, 30]}"#).unwrap();
+        assert_eq!(
+            value.remove_path("/a/b"),
+            Some(parse(r#"{"c": 1}"#).unwrap())
+        );
+        assert_eq!(value.remove_path("/e/1"), Some(Value::Number(20.0)));
+        assert_eq!(value, parse(r#"{"a": {"d": 2}, "e": [10, 30]}"#).unwrap());
+
+        assert_eq!(value.remove_path("/a/b"), None);
+        assert_eq!(value.remove_path("/e/2"), None);
+        assert_eq!(value.remove_path("/e/01"), None);
+        assert_eq!(value.remove_path("/a/d/x"), None);
+        assert_eq!(value.remove_path(""), None);
+        assert_eq!(value.remove_path("a"), None);
+        assert_eq!(value, parse(r#"{"a": {"d": 2}, "e": [10, 30]}"#).unwrap());
+    }
+
+    #[test]
+    fn test_paths_where() {
+        let value =
+            parse(r#"{"a": null, "b": [1, null, {"c/d": null, "e": 2}], "f": {"g": null}}"#)
+                .unwrap();
+        let paths = value.paths_where(|v| *v == Value::Null);
+        assert_eq!(paths, vec!["/a", "/b/1", "/b/2/c~1d", "/f/g"]);
+        for path in &paths {
+            assert_eq!(value.pointer(path), Some(&Value::Null));
+        }
+
+        assert_eq!(
+            value.paths_where(|v| v.is_container()),
+            vec!["", "/b", "/b/2", "/f"]
+        );
+        assert!(value.paths_where(|v| *v == Value::Bool(true)).is_empty());
+
+        // Raw は Parse した Value の子要素もたどる
+        let value = Value::Array(vec![Value::raw(r#"{"x": null}"#).unwrap()]);
+        assert_eq!(value.paths_where(|v| *v == Value::Null), vec!["/0/x"]);
+        assert_eq!(value.paths_where(|v| v.is_container()), vec!["", "/0"]);
+    }
+
+    #[test]
+    fn test_leaves() {
+        let value = parse(
+            r#"{"b": [1, {"c/d": null, "e": "x"}, []], "a": true, "f": {}, "g": {"h": [2.5]}}"#,
+        )
+        .unwrap();
+        let leaves = value.leaves().collect::<Vec<_>>();
+        assert_eq!(
+            leaves,
+            vec![
+                ("/a".to_string(), &Value::Bool(true)),
+                ("/b/0".to_string(), &Value::Number(1.0)),
+                ("/b/1/c~1d".to_string(), &Value::Null),
+                ("/b/1/e".to_string(), &Value::String("x".to_string())),
+                ("/g/h/0".to_string(), &Value::Number(2.5)),
+            ]
+        );
+        for (path, leaf) in leaves {
+            assert_eq!(value.pointer(&path), Some(leaf));
+        }
+
+        // ルートが末端の場合はルート自身を返す
+        assert_eq!(
+            Value::Null.leaves().collect::<Vec<_>>(),
+            vec![("".to_string(), &Value::Null)]
+        );
+        assert_eq!(parse("[[], {}]").unwrap().leaves().count(), 0);
+
+        // 必要な分だけたどる
+        let value = Value::Array(vec![Value::Array(
+            (0..10_000).map(|i| Value::Number(i as f64)).collect(),
+        )]);
+        let mut leaves = value.leaves();
+        assert_eq!(
+            leaves.next(),
+            Some(("/0/0".to_string(), &Value::Number(0.0)))
+        );
+        assert_eq!(
+            leaves.nth(9_998),
+            Some(("/0/9999".to_string(), &Value::Number(9999.0)))
+        );
+        assert_eq!(leaves.next(), None);
+
+        // スカラーの Raw は Raw のまま返す
+        let value = Value::Array(vec![Value::raw("1").unwrap(), Value::raw("[]").unwrap()]);
+        let leaves = value.leaves().collect::<Vec<_>>();
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(leaves[0].0, "/0");
+        assert!(matches!(leaves[0].1, Value::Raw(_)));
+    }
+
+    #[test]
+    fn test_eq_ignoring() {
+        let a =
+            parse(r#"{"id": 1, "timestamp": "2022-01-01", "items": [{"v": 1, "at": 1}]}"#).unwrap();
+        let b =
+            parse(r#"{"id": 1, "timestamp": "2023-12-31", "items": [{"v": 1, "at": 2}]}"#).unwrap();
+        assert!(!a.eq_ignoring(&b, &[]));
+        assert!(!a.eq_ignoring(&b, &["/timestamp"]));
+        assert!(a.eq_ignoring(&b, &["/timestamp", "/items/0/at"]));
+
+        // 片方にしか無い場所も無視できる
+        let c = parse(r#"{"id": 1, "items": [{"v": 1}]}"#).unwrap();
+        assert!(a.eq_ignoring(&c, &["/timestamp", "/items/0/at"]));
+        assert!(c.eq_ignoring(&a, &["/timestamp", "/items/0/at"]));
+
+        let d = parse(r#"{"id": 2, "items": [{"v": 1}]}"#).unwrap();
+        assert!(!c.eq_ignoring(&d, &["/timestamp"]));
+        assert!(c.eq_ignoring(&d, &[""]));
+    }
+}
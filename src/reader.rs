@@ -0,0 +1,140 @@
+use crate::{JsonError, Parser, ParserError, StreamLexer, Token, Value};
+
+/// 1 行ずつ入力を受け取り、完成した Value を返す Reader。
+/// 対話的な REPL で複数行に渡る JSON を入力するために使う
+pub struct JsonReader {
+    lexer: StreamLexer,
+    /// 完成していない Value の Token
+    tokens: Vec<Token>,
+    /// 閉じていない Array/Object の深さ
+    depth: usize,
+    /// エラーになった行で、エラーより前に完成していた Value
+    completed: Vec<Value>,
+}
+
+impl Default for JsonReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonReader {
+    pub fn new() -> Self {
+        JsonReader {
+            lexer: StreamLexer::new(),
+            tokens: vec![],
+            depth: 0,
+            completed: vec![],
+        }
+    }
+
+    /// 1 行を読み込み、この行で完成した Value を返す。
+    /// 空白だけの行や、Value が完成していない行では空の Vec を返す。
+    /// エラーの場合は読み込み途中の状態を破棄し、この行でエラーより前に完成していた Value は
+    /// take_completed で取り出せるように残す
+    pub fn push_line(&mut self, line: &str) -> Result<Vec<Value>, JsonError> {
+        let mut values = vec![];
+        match self.read_line(line, &mut values) {
+            Ok(()) => {
+                self.completed.clear();
+                Ok(values)
+            }
+            Err(e) => {
+                self.reset();
+                self.completed = values;
+                Err(e)
+            }
+        }
+    }
+
+    /// 直前の push_line がエラーになった場合に、その行でエラーより前に完成していた Value を取り出す
+    pub fn take_completed(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.completed)
+    }
+
+    /// 読み込み途中の Value があるか (REPL で続きの入力を促すために使う)
+    pub fn is_pending(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// 読み込み途中の状態を破棄する
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 1 行を読み込み、完成した Value を values に追加する
+    fn read_line(&mut self, line: &str, values: &mut Vec<Value>) -> Result<(), JsonError> {
+        self.lexer.feed(line)?;
+        self.lexer.feed("\n")?;
+
+        for token in self.lexer.drain_tokens() {
+            match token {
+                Token::LeftBrace | Token::LeftBracket => self.depth += 1,
+                Token::RightBrace | Token::RightBracket => {
+                    self.depth = self.depth.checked_sub(1).ok_or_else(|| {
+                        ParserError::new("error: unexpected closing bracket/brace at top level")
+                    })?;
+                }
+                _ => {}
+            }
+            self.tokens.push(token);
+
+            // 最上位の Value が閉じた
+            if self.depth == 0 {
+                let tokens = std::mem::take(&mut self.tokens);
+                values.push(Parser::new(tokens).into_value()?);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonReader;
+    use crate::{parse, Value};
+
+    #[test]
+    fn test_json_reader() {
+        let mut reader = JsonReader::new();
+        let lines = ["{", r#"  "a": [1,"#, "", "    2],", r#"  "b": "x""#];
+        for line in lines {
+            assert_eq!(reader.push_line(line).unwrap(), vec![]);
+            assert!(reader.is_pending());
+        }
+        assert_eq!(
+            reader.push_line("}").unwrap(),
+            vec![parse(r#"{"a": [1, 2], "b": "x"}"#).unwrap()]
+        );
+        assert!(!reader.is_pending());
+
+        // 空白だけの行
+        assert_eq!(reader.push_line("   ").unwrap(), vec![]);
+        // 1 行に複数の Value
+        assert_eq!(
+            reader.push_line("1 [true] null").unwrap(),
+            vec![
+                Value::Number(1.0),
+                Value::Array(vec![Value::Bool(true)]),
+                Value::Null
+            ]
+        );
+
+        // エラーの後は読み込み途中の状態が破棄される
+        reader.push_line("[1,").unwrap();
+        assert!(reader.push_line(":]").is_err());
+        assert!(!reader.is_pending());
+        assert!(reader.push_line("]").is_err());
+        assert_eq!(reader.push_line("2").unwrap(), vec![Value::Number(2.0)]);
+
+        // エラーより前に完成していた Value は取り出せる
+        assert!(reader.push_line("1 [2] ] 3").is_err());
+        assert_eq!(
+            reader.take_completed(),
+            vec![Value::Number(1.0), parse("[2]").unwrap()]
+        );
+        assert_eq!(reader.take_completed(), vec![]);
+        assert!(reader.push_line(r#"{"a": 1} [1 2]"#).is_err());
+        assert_eq!(reader.take_completed(), vec![parse(r#"{"a": 1}"#).unwrap()]);
+    }
+}
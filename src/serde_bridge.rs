@@ -0,0 +1,85 @@
+use crate::Value;
+
+impl From<Value> for serde_json::Value {
+    /// serde_json::Value に変換する。
+    /// 整数の数値は整数として、NaN や無限大は serde_json で表せないので null にする
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(b),
+            Value::Number(n) if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 => {
+                serde_json::Value::Number((n as i64).into())
+            }
+            Value::Number(n) => serde_json::Number::from_f64(n)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            #[cfg(feature = "decimal")]
+            Value::Decimal(d) => serde_json::from_str(&d.to_string()).unwrap_or_default(),
+            Value::String(s) => serde_json::Value::String(s),
+            Value::Array(array) => {
+                serde_json::Value::Array(array.into_iter().map(Into::into).collect())
+            }
+            Value::Object(object) => serde_json::Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+            Value::Raw(raw) => raw.into_value().into(),
+        }
+    }
+}
+
+impl From<serde_json::Value> for Value {
+    /// serde_json::Value から変換する。数値は f64 にする
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => n.as_f64().map_or(Value::Null, Value::Number),
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Array(array) => {
+                Value::Array(array.into_iter().map(Into::into).collect())
+            }
+            serde_json::Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, Value};
+
+    #[test]
+    fn test_serde_json_round_trip() {
+        let json = r#"{"name": "taro", "age": 20, "score": -1.5, "big": 12345678901234, "ok": true, "none": null, "tags": ["a", {"b": [1, 2.25]}], "empty": {}}"#;
+        let value = parse(json).unwrap();
+
+        let converted = serde_json::Value::from(value.clone());
+        assert_eq!(
+            converted,
+            serde_json::from_str::<serde_json::Value>(json).unwrap()
+        );
+        assert_eq!(converted["age"], serde_json::json!(20));
+        assert_eq!(converted["tags"][1]["b"][1], serde_json::json!(2.25));
+
+        assert_eq!(Value::from(converted), value);
+    }
+
+    #[test]
+    fn test_serde_json_non_finite() {
+        assert_eq!(
+            serde_json::Value::from(Value::Number(f64::NAN)),
+            serde_json::Value::Null
+        );
+        assert_eq!(
+            serde_json::Value::from(Value::raw(r#"{"a": [1]}"#).unwrap()),
+            serde_json::json!({"a": [1]})
+        );
+    }
+}
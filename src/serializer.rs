@@ -0,0 +1,643 @@
+use crate::{JsonError, Value, ValueError};
+use std::io::{self, Write};
+
+/// 整形して出力する場合の改行の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// \n
+    #[default]
+    Lf,
+    /// \r\n (Windows)
+    CrLf,
+}
+
+impl LineEnding {
+    fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+}
+
+/// JSON 文字列への変換の設定
+#[derive(Debug, Clone, Default)]
+pub struct SerializeOptions {
+    /// 1 段のインデントの空白の数。Some の場合は要素ごとに改行して整形する
+    pub indent: Option<usize>,
+    /// 改行の種類 (整形する場合と trailing_newline の改行に使う)
+    pub line_ending: LineEnding,
+    /// U+2028 (LINE SEPARATOR) と U+2029 (PARAGRAPH SEPARATOR) を \u2028 と \u2029 にエスケープする。
+    /// JSON では有効だが、JavaScript として eval すると文字列中の改行として扱われてしまう
+    pub escape_js_separators: bool,
+    /// 出力の末尾に改行を 1 つ付け加える
+    pub trailing_newline: bool,
+    /// 絶対値がこの範囲 [min, max) にある数値は小数表記 (e.g. 0.0001) で、
+    /// 範囲外の数値は指数表記 (e.g. 1e20) で書き出す。0 は常に小数表記になる。
+    /// None の場合は常に小数表記で書き出す
+    pub plain_number_range: Option<(f64, f64)>,
+    /// 整形する場合に、インデントを含めてこの文字数に収まる Array/Object は
+    /// 改行せずに 1 行 (e.g. [1, 2, 3]) で書き出す
+    pub max_inline_width: Option<usize>,
+    /// 整形する場合に、改行して書き出す Array/Object の最後の要素の後ろにも , を付ける。
+    /// 標準の JSON ではなくなるので、末尾の , を許可する読み手 (e.g. ParserOptions::allow_trailing_commas) 向けに使う
+    pub trailing_commas: bool,
+}
+
+/// 1 行で書き出す Array/Object を溜めておく書き出し先。
+/// 溜めた文字数が remaining を超えた時点で書き出しを失敗させる
+struct InlineBuffer {
+    bytes: Vec<u8>,
+    /// あと何文字書き出せるか
+    remaining: usize,
+}
+
+impl Write for InlineBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // UTF-8 の継続バイト以外を数えると文字数になる
+        let chars = buf.iter().filter(|&&b| b & 0xC0 != 0x80).count();
+        self.remaining = self.remaining.checked_sub(chars).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::WriteZero, "error: exceeds max_inline_width")
+        })?;
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Value を JSON 文字列として書き出す
+struct Serializer<W: Write> {
+    writer: W,
+    options: SerializeOptions,
+    /// 書き出し中の Array/Object の深さ
+    depth: usize,
+    /// 整形する場合に、改行せずに 1 行で書き出している最中か
+    inline: bool,
+}
+
+impl<W: Write> Serializer<W> {
+    fn with_options(writer: W, options: SerializeOptions) -> Serializer<W> {
+        Serializer {
+            writer,
+            options,
+            depth: 0,
+            inline: false,
+        }
+    }
+
+    /// 整形する場合は改行し、現在の深さの分だけインデントする
+    fn write_newline(&mut self) -> io::Result<()> {
+        if self.inline {
+            return Ok(());
+        }
+        if let Some(indent) = self.options.indent {
+            self.writer.write_all(self.options.line_ending.as_bytes())?;
+            write!(self.writer, "{:1$}", "", indent * self.depth)?;
+        }
+        Ok(())
+    }
+
+    /// Array/Object を 1 行で書き出してみて、max_inline_width に収まる場合は書き出して true を返す。
+    /// 収まらないと分かった時点で書き出すのをやめる
+    fn write_inline(&mut self, value: &Value) -> io::Result<bool> {
+        let (Some(indent), Some(width)) = (self.options.indent, self.options.max_inline_width)
+        else {
+            return Ok(false);
+        };
+        let Some(remaining) = width.checked_sub(indent * self.depth) else {
+            return Ok(false);
+        };
+        let mut buffer = InlineBuffer {
+            bytes: vec![],
+            remaining,
+        };
+        let mut serializer = Serializer::with_options(&mut buffer, self.options.clone());
+        serializer.inline = true;
+        // メモリへの書き出しが失敗するのは、幅に収まらない場合だけ
+        if serializer.write_value(value).is_err() {
+            return Ok(false);
+        }
+        self.writer.write_all(&buffer.bytes)?;
+        Ok(true)
+    }
+
+    /// trailing_commas の場合は、改行して書き出した Array/Object の最後の要素の後ろに , を書き出す
+    fn write_trailing_comma(&mut self) -> io::Result<()> {
+        if self.options.trailing_commas && self.options.indent.is_some() && !self.inline {
+            self.writer.write_all(b",")?;
+        }
+        Ok(())
+    }
+
+    /// 要素の区切りの , を書き出す
+    fn write_comma(&mut self) -> io::Result<()> {
+        self.writer
+            .write_all(if self.inline { b", " } else { b"," })
+    }
+
+    /// Value を再帰的に書き出す
+    fn write_value(&mut self, value: &Value) -> io::Result<()> {
+        if !self.inline
+            && matches!(value, Value::Array(_) | Value::Object(_))
+            && self.write_inline(value)?
+        {
+            return Ok(());
+        }
+        match value {
+            Value::String(s) => self.write_string(s),
+            Value::Number(n) => self.write_number(*n),
+            #[cfg(feature = "decimal")]
+            Value::Decimal(d) => write!(self.writer, "{}", d),
+            Value::Bool(b) => write!(self.writer, "{}", b),
+            Value::Null => self.writer.write_all(b"null"),
+            // 整形する場合もそのまま出力する
+            Value::Raw(raw) => self.writer.write_all(raw.as_str().as_bytes()),
+            // 空の Array/Object は整形する場合も 1 行にする
+            Value::Array(array) if array.is_empty() => self.writer.write_all(b"[]"),
+            Value::Object(object) if object.is_empty() => self.writer.write_all(b"{}"),
+            Value::Array(array) => {
+                self.writer.write_all(b"[")?;
+                self.depth += 1;
+                for (i, v) in array.iter().enumerate() {
+                    if i != 0 {
+                        self.write_comma()?;
+                    }
+                    self.write_newline()?;
+                    self.write_value(v)?;
+                }
+                self.write_trailing_comma()?;
+                self.depth -= 1;
+                self.write_newline()?;
+                self.writer.write_all(b"]")
+            }
+            Value::Object(object) => {
+                self.writer.write_all(b"{")?;
+                self.depth += 1;
+                for (i, (k, v)) in object.iter().enumerate() {
+                    if i != 0 {
+                        self.write_comma()?;
+                    }
+                    self.write_newline()?;
+                    self.write_string(k)?;
+                    self.writer.write_all(match self.options.indent {
+                        Some(_) => b": ",
+                        None => b":",
+                    })?;
+                    self.write_value(v)?;
+                }
+                self.write_trailing_comma()?;
+                self.depth -= 1;
+                self.write_newline()?;
+                self.writer.write_all(b"}")
+            }
+        }
+    }
+
+    /// 数値を書き出す。JSON では NaN や無限大を表現できないので null にする
+    fn write_number(&mut self, n: f64) -> io::Result<()> {
+        if n.is_finite() {
+            match self.options.plain_number_range {
+                Some((min, max)) if n != 0.0 && !(min..max).contains(&n.abs()) => {
+                    write!(self.writer, "{:e}", n)
+                }
+                _ => write!(self.writer, "{}", n),
+            }
+        } else {
+            self.writer.write_all(b"null")
+        }
+    }
+
+    /// 文字列を " で囲み、" と \ と制御文字をエスケープして書き出す
+    fn write_string(&mut self, s: &str) -> io::Result<()> {
+        let bytes = s.as_bytes();
+        self.writer.write_all(b"\"")?;
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            let escaped = match c {
+                '"' => Some("\\\""),
+                '\\' => Some("\\\\"),
+                '\u{0008}' => Some("\\b"),
+                '\u{000C}' => Some("\\f"),
+                '\n' => Some("\\n"),
+                '\r' => Some("\\r"),
+                '\t' => Some("\\t"),
+                // それ以外の制御文字は \uXXXX にする
+                c if (c as u32) < 0x20 => None,
+                '\u{2028}' | '\u{2029}' if self.options.escape_js_separators => None,
+                _ => continue,
+            };
+            // エスケープが不要な部分はまとめて書き出す
+            self.writer.write_all(&bytes[start..i])?;
+            match escaped {
+                Some(escaped) => self.writer.write_all(escaped.as_bytes())?,
+                None => write!(self.writer, "\\u{:04x}", c as u32)?,
+            }
+            start = i + c.len_utf8();
+        }
+        self.writer.write_all(&bytes[start..])?;
+        self.writer.write_all(b"\"")
+    }
+}
+
+impl Value {
+    /// 改行や空白を含まない JSON 文字列に変換する。
+    /// Object のキーは BTreeMap に格納されているので、常にキーの昇順で出力される
+    pub fn to_json_string(&self) -> String {
+        self.to_json_string_with_options(SerializeOptions::default())
+    }
+
+    /// 設定に従って JSON 文字列に変換する
+    pub fn to_json_string_with_options(&self, options: SerializeOptions) -> String {
+        let mut buffer = vec![];
+        self.write_json(&mut buffer, options)
+            .expect("error: writing to a Vec never fails");
+        String::from_utf8(buffer).expect("error: the serializer only writes UTF-8")
+    }
+
+    /// 改行や空白を含まない JSON を UTF-8 のバイト列として返す。
+    /// String を経由しないので、そのまま送信するような場合に使う
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        self.write_json(&mut buffer, SerializeOptions::default())
+            .expect("error: writing to a Vec never fails");
+        buffer
+    }
+
+    /// 設定に従って JSON を writer に書き出す (e.g. ファイルやソケットへの書き出し)。
+    /// 全体を一度メモリ上に作ることはしない
+    pub fn write_json<W: Write>(&self, writer: W, options: SerializeOptions) -> io::Result<()> {
+        let trailing_newline = options.trailing_newline.then_some(options.line_ending);
+        let mut serializer = Serializer::with_options(writer, options);
+        serializer.write_value(self)?;
+        if let Some(line_ending) = trailing_newline {
+            serializer.writer.write_all(line_ending.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// 2 つの空白でインデントし、要素ごとに改行して整形した JSON 文字列に変換する
+    pub fn to_pretty_string(&self) -> String {
+        self.to_json_string_pretty(2)
+    }
+
+    /// 1 段ごとに indent 個の空白でインデントし、要素ごとに改行して整形した JSON 文字列に変換する。
+    /// 空の Array/Object は [] と {} のまま 1 行で出力する
+    pub fn to_json_string_pretty(&self, indent: usize) -> String {
+        self.to_json_string_with_options(SerializeOptions {
+            indent: Some(indent),
+            ..SerializeOptions::default()
+        })
+    }
+
+    /// Array の各要素を 1 行ずつの JSON 文字列にした JSON Lines (NDJSON) に変換する。
+    /// 各行は改行で終わる。self が Array でない場合はエラーを返す
+    pub fn to_ndjson(&self) -> Result<String, JsonError> {
+        match self {
+            Value::Array(array) => Ok(array.iter().map(|v| v.to_json_string() + "\n").collect()),
+            _ => Err(ValueError::new(&format!(
+                "error: NDJSON can only be made from an array, not {}",
+                self.type_name()
+            ))
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineEnding, SerializeOptions};
+    use crate::{parse, Lexer, Parser, ParserOptions, Value};
+
+    #[test]
+    fn test_to_json_string() {
+        let value =
+            parse(r#"{"b": [1, 2.5, -3e2, true, null], "a": {"c": "x"}, "d": []}"#).unwrap();
+        assert_eq!(
+            value.to_json_string(),
+            r#"{"a":{"c":"x"},"b":[1,2.5,-300,true,null],"d":[]}"#
+        );
+
+        let value = Value::String("\"quote\" \\ \n\t\u{1}あ".to_string());
+        assert_eq!(value.to_json_string(), r#""\"quote\" \\ \n\t\u0001あ""#);
+        assert_eq!(Value::Number(f64::NAN).to_json_string(), "null");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let jsons = [
+            r#"{"users": [{"name": "taro", "age": 20, "tags": ["a", "b"]}, {"name": null}], "meta": {"nested": {"deep": [[], {}, [1, [2, [3]]]]}}}"#,
+            r#"[0.1, -0.5, 1e-7, 1.7976931348623157e308, 5e-324, 123456789012345680000]"#,
+            r#"["line1\nline2", "\"quote\" \\ \/ \b\f\r\t", "\u0001\u001f", "あ😄"]"#,
+            r#"{"": {"a\"b": [true, false, null]}}"#,
+        ];
+        jsons.into_iter().for_each(|json| {
+            let value = parse(json).unwrap();
+            let serialized = value.to_json_string();
+            assert_eq!(parse(&serialized).unwrap(), value, "{}", serialized);
+        });
+    }
+
+    #[test]
+    fn test_escape_js_separators() {
+        let value = Value::String("a\u{2028}b\u{2029}c".to_string());
+        assert_eq!(value.to_json_string(), "\"a\u{2028}b\u{2029}c\"");
+
+        let options = SerializeOptions {
+            escape_js_separators: true,
+            ..SerializeOptions::default()
+        };
+        assert_eq!(
+            value.to_json_string_with_options(options),
+            r#""a\u2028b\u2029c""#
+        );
+    }
+
+    #[test]
+    fn test_trailing_newline() {
+        let value = parse(r#"{"a": [1, "x"]}"#).unwrap();
+        assert_eq!(value.to_json_string(), r#"{"a":[1,"x"]}"#);
+
+        let options = SerializeOptions {
+            trailing_newline: true,
+            ..SerializeOptions::default()
+        };
+        assert_eq!(
+            value.to_json_string_with_options(options),
+            "{\"a\":[1,\"x\"]}\n"
+        );
+    }
+
+    #[test]
+    fn test_plain_number_range() {
+        let value = parse("[1e20, 0.0001, 1000000, 0, -2.5e-7]").unwrap();
+        assert_eq!(
+            value.to_json_string(),
+            "[100000000000000000000,0.0001,1000000,0,-0.00000025]"
+        );
+
+        let options = SerializeOptions {
+            plain_number_range: Some((1e-6, 1e15)),
+            ..SerializeOptions::default()
+        };
+        let json = value.to_json_string_with_options(options);
+        assert_eq!(json, "[1e20,0.0001,1000000,0,-2.5e-7]");
+        assert_eq!(parse(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_raw() {
+        let raw = Value::raw(r#"{"cached": [1, 2]}"#).unwrap();
+        let value = Value::Array(vec![raw.clone(), Value::Null]);
+        // 中身はそのまま出力される
+        assert_eq!(value.to_json_string(), r#"[{"cached": [1, 2]},null]"#);
+        assert_eq!(
+            parse(&value.to_json_string()).unwrap(),
+            parse(r#"[{"cached": [1, 2]}, null]"#).unwrap()
+        );
+
+        // 比較は Parse した Value として行う
+        assert_eq!(raw, parse(r#"{"cached":[1,2]}"#).unwrap());
+        assert_eq!(raw, Value::raw(r#"{ "cached" : [1,2] }"#).unwrap());
+        assert_ne!(raw, Value::raw("[]").unwrap());
+        assert_eq!(
+            raw.hash_stable_u64(),
+            parse(r#"{"cached":[1,2]}"#).unwrap().hash_stable_u64()
+        );
+
+        assert!(Value::raw("[1,").is_err());
+        assert!(Value::raw("1 2").is_err());
+
+        // 中身の文字列と Parse した Value の両方を持つ
+        match &raw {
+            Value::Raw(raw) => {
+                assert_eq!(raw.as_str(), r#"{"cached": [1, 2]}"#);
+                assert_eq!(raw.value(), &parse(r#"{"cached":[1,2]}"#).unwrap());
+            }
+            _ => panic!("a raw value is expected"),
+        }
+        // 読み込みの操作では Parse した Value をたどる
+        assert!(raw.is_container());
+        assert_eq!(
+            raw.leaves()
+                .map(|(path, v)| (path, v.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("/cached/0".to_string(), Value::Number(1.0)),
+                ("/cached/1".to_string(), Value::Number(2.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_pretty_string() {
+        let value = parse(r#"{"a": [1, {"b": null}], "c": {}, "d": []}"#).unwrap();
+        let expected = r#"{
+  "a": [
+    1,
+    {
+      "b": null
+    }
+  ],
+  "c": {},
+  "d": []
+}"#;
+        assert_eq!(value.to_pretty_string(), expected);
+        assert_eq!(parse(&value.to_pretty_string()).unwrap(), value);
+
+        let options = SerializeOptions {
+            indent: Some(2),
+            line_ending: LineEnding::CrLf,
+            trailing_newline: true,
+            ..SerializeOptions::default()
+        };
+        assert_eq!(
+            value.to_json_string_with_options(options),
+            expected.replace('\n', "\r\n") + "\r\n"
+        );
+        assert_eq!(Value::Null.to_pretty_string(), "null");
+    }
+
+    #[test]
+    fn test_to_json_string_pretty() {
+        let value = parse(r#"{"b": {"c": [1, "x"], "d": {}}, "a": [], "e": null}"#).unwrap();
+        let expected = r#"{
+  "a": [],
+  "b": {
+    "c": [
+      1,
+      "x"
+    ],
+    "d": {}
+  },
+  "e": null
+}"#;
+        assert_eq!(value.to_json_string_pretty(2), expected);
+        assert_eq!(parse(expected).unwrap(), value);
+
+        let expected = r#"{
+    "a": [],
+    "b": {
+        "c": [
+            1,
+            "x"
+        ],
+        "d": {}
+    },
+    "e": null
+}"#;
+        assert_eq!(value.to_json_string_pretty(4), expected);
+        assert_eq!(parse(expected).unwrap(), value);
+    }
+
+    #[test]
+    fn test_max_inline_width() {
+        let value =
+            parse(r#"{"short": [1, 2, 3], "long": [1000000, 2000000, 3000000], "o": {"a": 1}}"#)
+                .unwrap();
+        let options = SerializeOptions {
+            indent: Some(2),
+            max_inline_width: Some(20),
+            ..SerializeOptions::default()
+        };
+        let json = value.to_json_string_with_options(options);
+        assert_eq!(
+            json,
+            r#"{
+  "long": [
+    1000000,
+    2000000,
+    3000000
+  ],
+  "o": {"a": 1},
+  "short": [1, 2, 3]
+}"#
+        );
+        assert_eq!(parse(&json).unwrap(), value);
+
+        // 全体が収まる場合は 1 行になる
+        let options = SerializeOptions {
+            indent: Some(2),
+            max_inline_width: Some(100),
+            ..SerializeOptions::default()
+        };
+        assert_eq!(
+            value.to_json_string_with_options(options),
+            r#"{"long": [1000000, 2000000, 3000000], "o": {"a": 1}, "short": [1, 2, 3]}"#
+        );
+
+        // 幅はバイト数ではなく文字数で数え、ちょうど収まる場合も 1 行になる
+        let short = parse(r#"["あい"]"#).unwrap();
+        let inline = |width| {
+            short.to_json_string_with_options(SerializeOptions {
+                indent: Some(2),
+                max_inline_width: Some(width),
+                ..SerializeOptions::default()
+            })
+        };
+        assert_eq!(inline(6), r#"["あい"]"#);
+        assert_eq!(inline(5), "[\n  \"あい\"\n]");
+
+        // 整形しない場合は影響しない
+        let options = SerializeOptions {
+            max_inline_width: Some(100),
+            ..SerializeOptions::default()
+        };
+        assert_eq!(
+            value.to_json_string_with_options(options),
+            value.to_json_string()
+        );
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let value = parse(r#"{"a": [1, 2], "b": {"c": []}}"#).unwrap();
+        let options = SerializeOptions {
+            indent: Some(2),
+            trailing_commas: true,
+            ..SerializeOptions::default()
+        };
+        let json = value.to_json_string_with_options(options);
+        assert_eq!(
+            json,
+            r#"{
+  "a": [
+    1,
+    2,
+  ],
+  "b": {
+    "c": [],
+  },
+}"#
+        );
+        assert!(parse(&json).is_err());
+        let parser_options = ParserOptions {
+            allow_trailing_commas: true,
+            ..ParserOptions::default()
+        };
+        let tokens = Lexer::new(&json).tokenize().unwrap();
+        assert_eq!(
+            Parser::with_options(tokens, parser_options)
+                .into_value()
+                .unwrap(),
+            value
+        );
+
+        // 1 行で書き出す部分や整形しない場合は付けない
+        let options = SerializeOptions {
+            indent: Some(2),
+            trailing_commas: true,
+            max_inline_width: Some(10),
+            ..SerializeOptions::default()
+        };
+        assert!(value
+            .to_json_string_with_options(options)
+            .contains(r#""a": [1, 2]"#));
+        let options = SerializeOptions {
+            trailing_commas: true,
+            ..SerializeOptions::default()
+        };
+        assert_eq!(
+            value.to_json_string_with_options(options),
+            value.to_json_string()
+        );
+        assert!(!value.to_pretty_string().contains(",\n}"));
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let value = parse(r#"{"a": [1, "あ\n", null], "b": {"c": true}}"#).unwrap();
+        assert_eq!(value.to_bytes(), value.to_json_string().into_bytes());
+
+        let mut buffer = vec![];
+        value
+            .write_json(&mut buffer, SerializeOptions::default())
+            .unwrap();
+        assert_eq!(buffer, value.to_bytes());
+
+        let options = SerializeOptions {
+            indent: Some(2),
+            trailing_newline: true,
+            ..SerializeOptions::default()
+        };
+        let mut buffer = vec![];
+        value.write_json(&mut buffer, options.clone()).unwrap();
+        assert_eq!(
+            buffer,
+            value.to_json_string_with_options(options).into_bytes()
+        );
+    }
+
+    #[test]
+    fn test_to_ndjson() {
+        let value = parse(r#"[{"id": 1, "name": "a"}, {"id": 2, "tags": []}, null]"#).unwrap();
+        assert_eq!(
+            value.to_ndjson().unwrap(),
+            "{\"id\":1,\"name\":\"a\"}\n{\"id\":2,\"tags\":[]}\nnull\n"
+        );
+        assert_eq!(Value::Array(vec![]).to_ndjson().unwrap(), "");
+        assert!(parse(r#"{"a": 1}"#).unwrap().to_ndjson().is_err());
+    }
+}
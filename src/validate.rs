@@ -0,0 +1,179 @@
+use crate::{JsonError, Lexer, ParserError, Token};
+
+/// 次に受け付ける Token
+#[derive(Clone, Copy, PartialEq)]
+enum Expect {
+    /// Value
+    Value,
+    /// Value もしくは ] ([ の直後)
+    ValueOrClose,
+    /// キーもしくは } ({ の直後)
+    KeyOrClose,
+    /// キー
+    Key,
+    /// :
+    Colon,
+    /// , もしくは閉じ括弧
+    CommaOrClose,
+    /// 入力の終わり
+    End,
+}
+
+/// 閉じていない Array/Object
+#[derive(Clone, Copy, PartialEq)]
+enum Container {
+    Array,
+    Object,
+}
+
+/// Value を組み立てずに JSON 文字列が正しいかを検証し、
+/// Array/Object の入れ子の深さが max_depth 以下で、Value の総数が max_elements 以下であることを確かめる。
+/// Array/Object 自身も 1 つの Value として数える。
+/// 深さは Value::depth と同じく、スカラー値や空の Array/Object のルートを 1 とする
+pub fn validate_with_limits(
+    input: &str,
+    max_depth: usize,
+    max_elements: usize,
+) -> Result<(), JsonError> {
+    let mut lexer = Lexer::new(input);
+    let mut stack = vec![];
+    let mut expect = Expect::Value;
+    let mut elements = 0;
+
+    while let Some((token, _)) = lexer.next_spanned_token()? {
+        let top = stack.last().copied();
+        expect = match (expect, token) {
+            (_, Token::WhiteSpace) => expect,
+            (Expect::Value | Expect::ValueOrClose, token) if is_value_start(&token) => {
+                elements += 1;
+                if elements > max_elements {
+                    return Err(ParserError::new(&format!(
+                        "error: exceeded the max elements {}",
+                        max_elements
+                    ))
+                    .into());
+                }
+                // この Value の深さは、閉じていない Array/Object の数 + 1
+                if stack.len() >= max_depth {
+                    return Err(ParserError::new(&format!(
+                        "error: exceeded the max depth {}",
+                        max_depth
+                    ))
+                    .into());
+                }
+                match token {
+                    Token::LeftBracket | Token::LeftBrace => {
+                        if token == Token::LeftBracket {
+                            stack.push(Container::Array);
+                            Expect::ValueOrClose
+                        } else {
+                            stack.push(Container::Object);
+                            Expect::KeyOrClose
+                        }
+                    }
+                    _ => after_value(&stack),
+                }
+            }
+            (Expect::Key | Expect::KeyOrClose, Token::String(_)) => Expect::Colon,
+            (Expect::Colon, Token::Colon) => Expect::Value,
+            (Expect::CommaOrClose, Token::Comma) => match top {
+                Some(Container::Array) => Expect::Value,
+                _ => Expect::Key,
+            },
+            (Expect::ValueOrClose | Expect::CommaOrClose, Token::RightBracket)
+                if top == Some(Container::Array) =>
+            {
+                stack.pop();
+                after_value(&stack)
+            }
+            (Expect::KeyOrClose | Expect::CommaOrClose, Token::RightBrace)
+                if top == Some(Container::Object) =>
+            {
+                stack.pop();
+                after_value(&stack)
+            }
+            (_, token) => {
+                return Err(
+                    ParserError::new(&format!("error: unexpected token {:?}", token)).into(),
+                )
+            }
+        };
+    }
+
+    if expect != Expect::End {
+        return Err(ParserError::new("error: unexpected end of input").into());
+    }
+    Ok(())
+}
+
+/// Value の先頭になる Token か
+fn is_value_start(token: &Token) -> bool {
+    !matches!(
+        token,
+        Token::WhiteSpace | Token::RightBrace | Token::RightBracket | Token::Comma | Token::Colon
+    )
+}
+
+/// Value を読み終えた後に受け付ける Token
+fn after_value(stack: &[Container]) -> Expect {
+    if stack.is_empty() {
+        Expect::End
+    } else {
+        Expect::CommaOrClose
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_with_limits;
+
+    #[test]
+    fn test_validate_with_limits() {
+        let json = r#"{"a": [1, {"b": null}], "c": "x"}"#;
+        // Value は Object, [..], 1, {..}, null, "x" の 6 個で、深さは 4 (Object, [..], {..}, null)
+        assert!(validate_with_limits(json, 4, 6).is_ok());
+        assert!(validate_with_limits(json, 3, 6).is_err());
+        assert!(validate_with_limits(json, 4, 5).is_err());
+
+        assert!(validate_with_limits("1", 1, 1).is_ok());
+        assert!(validate_with_limits("1", 0, 1).is_err());
+        assert!(validate_with_limits("[]", 1, 10).is_ok());
+        assert!(validate_with_limits("[]", 0, 10).is_err());
+        assert!(validate_with_limits("[[[[[[1]]]]]]", 7, 100).is_ok());
+        assert!(validate_with_limits("[[[[[[1]]]]]]", 6, 100).is_err());
+        assert!(validate_with_limits("[1, 2, 3]", 10, 3).is_err());
+
+        // Value::depth と同じ数え方なので、その深さを上限にすれば受け付ける
+        for json in [json, "1", "[]", "[[1], {}]", r#"{"a": {"b": []}}"#] {
+            let depth = crate::parse(json).unwrap().depth();
+            assert!(validate_with_limits(json, depth, 100).is_ok(), "{}", json);
+            assert!(
+                validate_with_limits(json, depth - 1, 100).is_err(),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_with_limits_malformed() {
+        for json in [
+            "",
+            "[1, 2",
+            "[1 2]",
+            "[1,]",
+            r#"{"a" 1}"#,
+            r#"{"a": 1,}"#,
+            "{1: 2}",
+            "[}",
+            "1 2",
+            "]",
+            r#""abc"#,
+        ] {
+            assert!(validate_with_limits(json, 10, 10).is_err(), "{}", json);
+        }
+        for json in ["[]", "{}", r#"{"a": [], "b": {}}"#, r#" "s" "#] {
+            assert!(validate_with_limits(json, 10, 10).is_ok(), "{}", json);
+        }
+    }
+}
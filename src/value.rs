@@ -0,0 +1,1467 @@
+use crate::{pointer::escape_token, JsonError, Value, ValueError};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// merge で両方の値が Array だった場合の結合方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayMergeStrategy {
+    /// other の Array で置き換える
+    Replace,
+    /// self の Array の後ろに other の要素を連結する
+    Concat,
+    /// Concat と同様だが、self に既に存在する要素は追加しない
+    Union,
+}
+
+/// sanitize で NaN や無限大の数値をどう扱うか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonFinitePolicy {
+    /// null に置き換える
+    #[default]
+    Null,
+    /// Array の要素や Object の値から取り除く (ルートの場合は null に置き換える)
+    Remove,
+}
+
+/// escape_for_sql でエスケープする SQL の方言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SqlDialect {
+    /// 標準 SQL (PostgreSQL の standard_conforming_strings が on の場合や SQLite)。
+    /// ' だけを '' にし、\ はそのまま残す。文字列リテラルの中で制御文字を表す方法がないので、制御文字はエラーにする
+    #[default]
+    Standard,
+    /// MySQL (NO_BACKSLASH_ESCAPES が無効な場合)。
+    /// ' と \ に加えて、MySQL のエスケープ (\0, \b, \n, \r, \t, \Z) で表せる制御文字を置き換える。
+    /// それ以外の制御文字 (e.g. \x1b や DEL) はエラーにする
+    MySql,
+}
+
+/// sanitize の設定
+#[derive(Debug, Clone, Default)]
+pub struct SanitizeOptions {
+    /// NaN や無限大の数値の扱い
+    pub non_finite: NonFinitePolicy,
+    /// 不正なバイト列から変換された (U+FFFD REPLACEMENT CHARACTER を含む) キーや文字列を持つ要素を取り除く
+    pub remove_replacement_chars: bool,
+}
+
+impl Value {
+    /// Raw の場合は Parse した Value を、それ以外は自身を返す。読み込みの操作は Raw をこの Value として扱う
+    pub(crate) fn resolved(&self) -> &Value {
+        match self {
+            Value::Raw(raw) => raw.value(),
+            value => value,
+        }
+    }
+
+    /// Raw の場合は Parse した Value に置き換えてから、変更可能な参照を返す。
+    /// 変更すると元の JSON 文字列とは一致しなくなるので、変更する操作は Raw をこの形にしてから変更する
+    pub(crate) fn resolved_mut(&mut self) -> &mut Value {
+        if let Value::Raw(raw) = self {
+            *self = raw.value().clone();
+        }
+        self
+    }
+
+    /// Raw の場合は Parse した Value を、それ以外は自身を返す
+    pub(crate) fn into_resolved(self) -> Value {
+        match self {
+            Value::Raw(raw) => raw.into_value(),
+            value => value,
+        }
+    }
+
+    /// Object から key に対応する Value を返す。
+    /// key が存在しない、もしくは self が Object でない場合は None を返す
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self.resolved() {
+            Value::Object(map) => map.get(key),
+            _ => None,
+        }
+    }
+
+    /// 文字列・数値・真偽値・null のいずれかか。Raw は Parse した Value で判定する
+    pub fn is_scalar(&self) -> bool {
+        !self.is_container()
+    }
+
+    /// Array か Object か。Raw は Parse した Value で判定する
+    pub fn is_container(&self) -> bool {
+        matches!(self.resolved(), Value::Array(_) | Value::Object(_))
+    }
+
+    /// Object から key に ASCII の大文字小文字を区別せずに一致する Value を返す (e.g. HTTP ヘッダ)。
+    /// 一致するキーが複数ある場合は、キーの昇順で最初のもの (e.g. "Content-Type" と "content-type" なら前者) を返す
+    pub fn get_ci(&self, key: &str) -> Option<&Value> {
+        match self.resolved() {
+            Value::Object(map) => map
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// 文字列を base64 としてデコードしたバイト列を返す。
+    /// 文字列でない、もしくは base64 として不正な場合は None を返す
+    #[cfg(feature = "base64")]
+    pub fn as_base64_bytes(&self) -> Option<Vec<u8>> {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        match self.resolved() {
+            Value::String(s) => STANDARD.decode(s).ok(),
+            _ => None,
+        }
+    }
+
+    /// 文字列を RFC3339 (ISO8601) 形式の日時として UTC で返す。
+    /// 文字列でない、もしくは日時として解釈できない場合は None を返す
+    #[cfg(feature = "chrono")]
+    pub fn as_datetime(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        match self.resolved() {
+            Value::String(s) => chrono::DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|datetime| datetime.with_timezone(&chrono::Utc)),
+            _ => None,
+        }
+    }
+
+    /// 文字列を dialect の SQL の文字列リテラルやログに埋め込めるようにエスケープして返す (' で囲むのは呼び出し側)。
+    /// あくまで表示・ログ向けの補助であり、パラメータ化クエリ (プレースホルダ) の代わりにはならない。
+    /// 制御文字 (U+0000..U+001F と DEL) は見える形のエスケープにするか、できない場合はエラーにするので、そのまま出力されることはない。
+    /// 文字列でない場合もエラーを返す
+    pub fn escape_for_sql(&self, dialect: SqlDialect) -> Result<String, JsonError> {
+        let s = match self.resolved() {
+            Value::String(s) => s,
+            value => {
+                return Err(ValueError::new(&format!(
+                    "error: only a string can be escaped for SQL, not {}",
+                    value.type_name()
+                ))
+                .into())
+            }
+        };
+        let mut result = String::with_capacity(s.len());
+        for c in s.chars() {
+            let escaped = match (dialect, c) {
+                (_, '\'') => "''",
+                (SqlDialect::MySql, '\\') => "\\\\",
+                (SqlDialect::MySql, '\0') => "\\0",
+                (SqlDialect::MySql, '\u{8}') => "\\b",
+                (SqlDialect::MySql, '\n') => "\\n",
+                (SqlDialect::MySql, '\r') => "\\r",
+                (SqlDialect::MySql, '\t') => "\\t",
+                (SqlDialect::MySql, '\u{1a}') => "\\Z",
+                (_, c) if c.is_ascii_control() => {
+                    return Err(ValueError::new(&format!(
+                        "error: U+{:04X} cannot be escaped in a {:?} SQL string literal",
+                        c as u32, dialect
+                    ))
+                    .into())
+                }
+                (_, c) => {
+                    result.push(c);
+                    continue;
+                }
+            };
+            result.push_str(escaped);
+        }
+        Ok(result)
+    }
+
+    /// key に対応する Value を返す。
+    /// key が存在しない、もしくは self が Object でない場合は default を返す
+    pub fn get_or<'a>(&'a self, key: &str, default: &'a Value) -> &'a Value {
+        self.get(key).unwrap_or(default)
+    }
+
+    /// key に対応する真偽値を返す。key が存在しない、もしくは真偽値でない場合は default を返す
+    pub fn get_bool_or(&self, key: &str, default: bool) -> bool {
+        match self.get(key).map(Value::resolved) {
+            Some(Value::Bool(b)) => *b,
+            _ => default,
+        }
+    }
+
+    /// key に対応する文字列を返す。key が存在しない、もしくは文字列でない場合は default を返す
+    pub fn get_str_or<'a>(&'a self, key: &str, default: &'a str) -> &'a str {
+        self.get(key).and_then(|v| v.as_str()).unwrap_or(default)
+    }
+
+    /// key に対応する数値を返す。key が存在しない、もしくは数値でない場合は default を返す
+    pub fn get_f64_or(&self, key: &str, default: f64) -> f64 {
+        match self.get(key).map(Value::resolved) {
+            Some(Value::Number(n)) => *n,
+            _ => default,
+        }
+    }
+
+    /// key に対応する Value を複製して T に変換する。
+    /// key が存在しない、もしくは T に変換できない場合は None を返す
+    pub fn try_get<T: TryFrom<Value>>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|v| T::try_from(v.clone()).ok())
+    }
+
+    /// Array の要素数を返す。Array でない場合は None を返す
+    pub fn get_array_len(&self) -> Option<usize> {
+        match self.resolved() {
+            Value::Array(array) => Some(array.len()),
+            _ => None,
+        }
+    }
+
+    /// Object のキーの数を返す。Object でない場合は None を返す
+    pub fn get_object_len(&self) -> Option<usize> {
+        match self.resolved() {
+            Value::Object(object) => Some(object.len()),
+            _ => None,
+        }
+    }
+
+    /// Array の最初の要素を返す。Array でない、もしくは空の場合は None を返す
+    pub fn first(&self) -> Option<&Value> {
+        match self.resolved() {
+            Value::Array(array) => array.first(),
+            _ => None,
+        }
+    }
+
+    /// Array の最後の要素を返す。Array でない、もしくは空の場合は None を返す
+    pub fn last(&self) -> Option<&Value> {
+        match self.resolved() {
+            Value::Array(array) => array.last(),
+            _ => None,
+        }
+    }
+
+    /// Array の先頭に v を追加する。self が Array でない場合は何もしない
+    pub fn prepend(&mut self, v: Value) {
+        if let Value::Array(array) = self.resolved_mut() {
+            array.insert(0, v);
+        }
+    }
+
+    /// Array の idx 番目に v を挿入し、それ以降の要素を後ろにずらす。idx が要素数と等しい場合は末尾に追加する。
+    /// self が Array でない、もしくは idx が要素数より大きい場合はエラーを返す
+    pub fn insert_at(&mut self, idx: usize, v: Value) -> Result<(), JsonError> {
+        match self.resolved_mut() {
+            Value::Array(array) if idx <= array.len() => {
+                array.insert(idx, v);
+                Ok(())
+            }
+            Value::Array(array) => Err(ValueError::new(&format!(
+                "error: an index {} is out of range for an array of length {}",
+                idx,
+                array.len()
+            ))
+            .into()),
+            _ => Err(ValueError::new(&format!(
+                "error: an element can only be inserted into an array, not {}",
+                self.type_name()
+            ))
+            .into()),
+        }
+    }
+
+    /// 文字列であれば借用して返す。文字列でない場合は None を返す
+    pub fn as_str(&self) -> Option<&str> {
+        match self.resolved() {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// 文字列であれば複製せずに所有権ごと返す。文字列でない場合は None を返す
+    pub fn into_string(self) -> Option<String> {
+        match self.into_resolved() {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// 全ての要素が文字列の Array であれば、各文字列を返す。
+    /// Array でない、もしくは文字列でない要素を含む場合は None を返す
+    pub fn as_str_array(&self) -> Option<Vec<&str>> {
+        match self.resolved() {
+            Value::Array(array) => array.iter().map(|v| v.as_str()).collect(),
+            _ => None,
+        }
+    }
+
+    /// 直下の子要素 (Array の要素 or Object の値) を返す。
+    /// それ以外の Value は子要素を持たないので空のイテレータになる
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        let (array, object) = match self.resolved() {
+            Value::Array(array) => (Some(array.iter()), None),
+            Value::Object(object) => (None, Some(object.values())),
+            _ => (None, None),
+        };
+        array
+            .into_iter()
+            .flatten()
+            .chain(object.into_iter().flatten())
+    }
+
+    /// 直下の子要素 (Array の要素 or Object の値) を変更可能な参照で返す。
+    /// それ以外の Value は子要素を持たないので空のイテレータになる。Raw は Parse した Value に置き換える
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        let (array, object) = match self.resolved_mut() {
+            Value::Array(array) => (Some(array.iter_mut()), None),
+            Value::Object(object) => (None, Some(object.values_mut())),
+            _ => (None, None),
+        };
+        array
+            .into_iter()
+            .flatten()
+            .chain(object.into_iter().flatten())
+    }
+
+    /// other を self にマージする。
+    /// 両方が Object の場合はキーごとに再帰的にマージし、それ以外は other で上書きする
+    pub fn merge(&mut self, other: Value) {
+        self.merge_with_strategy(other, ArrayMergeStrategy::Replace);
+    }
+
+    /// self と other を key の値で識別されるレコード (Object) の Array として扱い、other のレコードを upsert する。
+    /// key の値が等しいレコードが self にあれば merge し、無ければ末尾に追加する。
+    /// key を持たないレコード (Object でない要素を含む) は照合せずに末尾に追加する。
+    /// self と other のどちらかが Array でない場合は何もしない
+    pub fn merge_arrays_by_key(&mut self, other: &Value, key: &str) {
+        let (Value::Array(array), Value::Array(other)) = (self.resolved_mut(), other.resolved())
+        else {
+            return;
+        };
+        for record in other {
+            let existing = record.get(key).and_then(|id| {
+                array
+                    .iter_mut()
+                    .find(|existing| existing.get(key) == Some(id))
+            });
+            match existing {
+                Some(existing) => existing.merge(record.clone()),
+                None => array.push(record.clone()),
+            }
+        }
+    }
+
+    /// merge と同様だが、両方が Array の場合は strategy に従って結合する
+    pub fn merge_with_strategy(&mut self, other: Value, strategy: ArrayMergeStrategy) {
+        match (self.resolved_mut(), other.into_resolved()) {
+            (Value::Object(map), Value::Object(other)) => {
+                for (key, value) in other {
+                    match map.get_mut(&key) {
+                        Some(v) => v.merge_with_strategy(value, strategy),
+                        None => {
+                            map.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (Value::Array(array), Value::Array(other)) => match strategy {
+                ArrayMergeStrategy::Replace => *array = other,
+                ArrayMergeStrategy::Concat => array.extend(other),
+                ArrayMergeStrategy::Union => {
+                    for value in other {
+                        if !array.contains(&value) {
+                            array.push(value);
+                        }
+                    }
+                }
+            },
+            (this, other) => *this = other,
+        }
+    }
+
+    /// f を満たすノードの数を自身を含めて再帰的に数える
+    pub fn count_matching<F: Fn(&Value) -> bool>(&self, f: F) -> usize {
+        fn count<F: Fn(&Value) -> bool>(value: &Value, f: &F) -> usize {
+            value.iter().map(|v| count(v, f)).sum::<usize>() + usize::from(f(value))
+        }
+        count(self, &f)
+    }
+
+    /// 自身を含むノードの総数を返す
+    pub fn node_count(&self) -> usize {
+        self.count_matching(|_| true)
+    }
+
+    /// 入れ子になったものも含めた、Object のキーの総数を返す
+    pub fn key_count(&self) -> usize {
+        let keys = match self.resolved() {
+            Value::Object(object) => object.len(),
+            _ => 0,
+        };
+        keys + self.iter().map(|v| v.key_count()).sum::<usize>()
+    }
+
+    /// 自身を含むすべてのノードに、深さ優先 (親が先、子は Array/Object の順) で f を適用して畳み込む。
+    /// f がエラーを返した時点で中断し、そのエラーを返す
+    pub fn try_fold<B, E, F: FnMut(B, &Value) -> Result<B, E>>(
+        &self,
+        init: B,
+        mut f: F,
+    ) -> Result<B, E> {
+        fn fold<B, E, F: FnMut(B, &Value) -> Result<B, E>>(
+            value: &Value,
+            acc: B,
+            f: &mut F,
+        ) -> Result<B, E> {
+            let acc = f(acc, value)?;
+            value.iter().try_fold(acc, |acc, v| fold(v, acc, f))
+        }
+        fold(self, init, &mut f)
+    }
+
+    /// from と等しいすべてのノードを to に置き換える (e.g. 空文字列を null に)。
+    /// 置き換えたノードの中はそれ以上たどらない
+    pub fn replace_all(&mut self, from: &Value, to: &Value) {
+        if self == from {
+            *self = to.clone();
+        } else {
+            self.iter_mut().for_each(|v| v.replace_all(from, to));
+        }
+    }
+
+    /// needle と等しいノードが自身を含めてどこかに存在するかを返す
+    pub fn deep_contains(&self, needle: &Value) -> bool {
+        self == needle || self.iter().any(|v| v.deep_contains(needle))
+    }
+
+    /// Object のすべてのキーを f で再帰的に書き換える。
+    /// 書き換え後のキーが衝突した場合は、元のキーの順序 (昇順) で後ろにあったものが残る
+    pub fn map_keys<F: Fn(&str) -> String>(&mut self, f: F) {
+        fn map<F: Fn(&str) -> String>(value: &mut Value, f: &F) {
+            if let Value::Object(object) = value.resolved_mut() {
+                *object = std::mem::take(object)
+                    .into_iter()
+                    .map(|(key, value)| (f(&key), value))
+                    .collect();
+            }
+            value.iter_mut().for_each(|v| map(v, f));
+        }
+        map(self, &f);
+    }
+
+    /// Value を 64bit のハッシュ値に畳み込む。
+    /// FNV-1a を使い、文字列を組み立てずに木を走査するので割り当ては発生しない。
+    /// プロセスごとにランダムな値を使う std の Hasher とは異なり、同じ Value なら実行をまたいでも同じ値になる
+    pub fn hash_stable_u64(&self) -> u64 {
+        const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        fn write(hash: &mut u64, bytes: &[u8]) {
+            for byte in bytes {
+                *hash ^= u64::from(*byte);
+                *hash = hash.wrapping_mul(PRIME);
+            }
+        }
+
+        fn write_str(hash: &mut u64, s: &str) {
+            write(hash, &(s.len() as u64).to_le_bytes());
+            write(hash, s.as_bytes());
+        }
+
+        fn fold(hash: &mut u64, value: &Value) {
+            match value {
+                Value::String(s) => {
+                    write(hash, &[0]);
+                    write_str(hash, s);
+                }
+                Value::Number(n) => {
+                    write(hash, &[1]);
+                    // 0.0 == -0.0 なので同じハッシュ値にする
+                    let n = if *n == 0.0 { 0.0 } else { *n };
+                    write(hash, &n.to_bits().to_le_bytes());
+                }
+                #[cfg(feature = "decimal")]
+                Value::Decimal(d) => {
+                    write(hash, &[2]);
+                    write(hash, &d.normalize().serialize());
+                }
+                Value::Bool(b) => write(hash, &[3, u8::from(*b)]),
+                Value::Null => write(hash, &[4]),
+                Value::Array(array) => {
+                    write(hash, &[5]);
+                    write(hash, &(array.len() as u64).to_le_bytes());
+                    array.iter().for_each(|v| fold(hash, v));
+                }
+                Value::Object(object) => {
+                    write(hash, &[6]);
+                    write(hash, &(object.len() as u64).to_le_bytes());
+                    object.iter().for_each(|(k, v)| {
+                        write_str(hash, k);
+                        fold(hash, v);
+                    });
+                }
+                // 等しい Value と同じハッシュ値になるよう、Parse した Value のハッシュ値にする
+                Value::Raw(raw) => fold(hash, raw.value()),
+            }
+        }
+
+        let mut hash = OFFSET;
+        fold(&mut hash, self);
+        hash
+    }
+
+    /// merge と同様に other を self にマージするが、
+    /// 同じ位置の値の型が異なる場合 (e.g. 数値と文字列) はマージせずにエラーを返す
+    pub fn try_merge(&mut self, other: Value) -> Result<(), JsonError> {
+        fn check(this: &Value, other: &Value, path: &str) -> Result<(), JsonError> {
+            match (this.resolved(), other.resolved()) {
+                (Value::Object(this), Value::Object(other)) => {
+                    other
+                        .iter()
+                        .try_for_each(|(key, value)| match this.get(key) {
+                            Some(v) => check(v, value, &format!("{}/{}", path, escape_token(key))),
+                            None => Ok(()),
+                        })
+                }
+                _ if this.type_name() != other.type_name() => Err(ValueError::new(&format!(
+                    "error: cannot merge {} into {} at {}",
+                    other.type_name(),
+                    this.type_name(),
+                    if path.is_empty() { "/" } else { path }
+                ))
+                .into()),
+                _ => Ok(()),
+            }
+        }
+
+        check(self, &other, "")?;
+        self.merge(other);
+        Ok(())
+    }
+
+    /// Value 同士の全順序による比較。
+    /// 型の順 (null < bool < number < string < array < object) で比較し、同じ型の場合は値で比較する。
+    /// 数値は f64::total_cmp で比較する (NaN も順序を持つ)。Array は要素を、Object は (キー, 値) を先頭から順に比較する。
+    /// Raw は Parse した Value として比較する
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        fn rank(value: &Value) -> u8 {
+            match value {
+                Value::Null => 0,
+                Value::Bool(_) => 1,
+                Value::Number(_) => 2,
+                #[cfg(feature = "decimal")]
+                Value::Decimal(_) => 2,
+                Value::String(_) => 3,
+                Value::Array(_) => 4,
+                Value::Object(_) => 5,
+                Value::Raw(raw) => rank(raw.value()),
+            }
+        }
+
+        #[cfg(feature = "decimal")]
+        fn decimal_to_f64(d: &rust_decimal::Decimal) -> f64 {
+            d.to_string().parse().unwrap_or(f64::NAN)
+        }
+
+        match (self, other) {
+            (Value::Raw(a), _) => a.value().total_cmp(other),
+            (_, Value::Raw(b)) => self.total_cmp(b.value()),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+            #[cfg(feature = "decimal")]
+            (Value::Decimal(a), Value::Decimal(b)) => a.cmp(b),
+            #[cfg(feature = "decimal")]
+            (Value::Decimal(a), Value::Number(b)) => decimal_to_f64(a).total_cmp(b),
+            #[cfg(feature = "decimal")]
+            (Value::Number(a), Value::Decimal(b)) => a.total_cmp(&decimal_to_f64(b)),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Array(a), Value::Array(b)) => a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| a.total_cmp(b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            (Value::Object(a), Value::Object(b)) => a
+                .iter()
+                .zip(b)
+                .map(|((ak, av), (bk, bv))| ak.cmp(bk).then_with(|| av.total_cmp(bv)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+
+    /// スナップショットテストなどで比較できるよう、木を決定的な形に揃える。
+    /// Object のキーは常に昇順なので、sort_arrays の場合は全ての Array の要素も total_cmp の順に並べ替える。
+    /// 等しい Value が同じ形になるよう、Raw は Parse した Value に置き換える
+    pub fn canonicalize(&mut self, sort_arrays: bool) {
+        self.iter_mut().for_each(|v| v.canonicalize(sort_arrays));
+        if let Value::Array(array) = self.resolved_mut() {
+            if sort_arrays {
+                array.sort_by(|a, b| a.total_cmp(b));
+            }
+        }
+    }
+
+    /// Value の型の名前を返す。Raw は Parse した Value の型の名前になる
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self.resolved() {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            #[cfg(feature = "decimal")]
+            Value::Decimal(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::Null => "null",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+            Value::Raw(raw) => raw.value().type_name(),
+        }
+    }
+
+    /// JSON として出力できない NaN や無限大の数値を null に置き換える
+    pub fn sanitize(&mut self) {
+        self.sanitize_with_options(&SanitizeOptions::default());
+    }
+
+    /// 設定に従って、JSON として出力するのに不適切な要素を置き換える、もしくは取り除く
+    pub fn sanitize_with_options(&mut self, options: &SanitizeOptions) {
+        let has_replacement = |s: &str| options.remove_replacement_chars && s.contains('\u{FFFD}');
+        // 取り除くべき要素か
+        let is_invalid = |value: &Value| match value {
+            Value::Number(n) => !n.is_finite() && options.non_finite == NonFinitePolicy::Remove,
+            Value::String(s) => has_replacement(s),
+            _ => false,
+        };
+
+        let this = self.resolved_mut();
+        match this {
+            Value::Number(n) if !n.is_finite() => *this = Value::Null,
+            Value::Array(array) => {
+                array.retain(|v| !is_invalid(v));
+                array
+                    .iter_mut()
+                    .for_each(|v| v.sanitize_with_options(options));
+            }
+            Value::Object(object) => {
+                object.retain(|k, v| !is_invalid(v) && !has_replacement(k));
+                object
+                    .values_mut()
+                    .for_each(|v| v.sanitize_with_options(options));
+            }
+            _ => {}
+        }
+    }
+
+    /// 木の最大の深さを返す。スカラー値や空の Array/Object は 1 になる。
+    /// validate_with_limits の max_depth も同じ数え方をする
+    pub fn depth(&self) -> usize {
+        1 + self.iter().map(|v| v.depth()).max().unwrap_or(0)
+    }
+
+    /// Object のキーと値の組をキーの昇順で返す。
+    /// Object の格納方法 (現在は BTreeMap) に依らず順序が決まるようにソートする。
+    /// self が Object でない場合は空の Vec を返す
+    pub fn object_sorted(&self) -> Vec<(&String, &Value)> {
+        let mut entries = match self.resolved() {
+            Value::Object(object) => object.iter().collect::<Vec<_>>(),
+            _ => vec![],
+        };
+        entries.sort_by_key(|(key, _)| *key);
+        entries
+    }
+
+    /// 数値や真偽値、null に見える文字列を、対応する Value に再帰的に変換する (CSV 由来のデータ向け)。
+    /// - "true" / "false" / "null" (小文字のみ) は Bool / Null にする
+    /// - 数字と + - . e E だけからなり、有限の f64 として読める文字列は Number にする
+    /// - ただし "007" のように先頭が 0 で始まる整数部を持つものは ID や郵便番号の可能性が高いので変換しない
+    ///
+    /// 文字列として扱うべき値 (e.g. 桁数の多い ID) も変換されて精度が落ちる可能性があるので、
+    /// データの性質が分かっている場合にだけ使うこと
+    pub fn coerce_scalars(&mut self) {
+        let this = self.resolved_mut();
+        if let Value::String(s) = this {
+            let looks_like_number = !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E'))
+                && !s
+                    .trim_start_matches(['+', '-'])
+                    .strip_prefix('0')
+                    .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()));
+            let coerced = match s.as_str() {
+                "true" => Some(Value::Bool(true)),
+                "false" => Some(Value::Bool(false)),
+                "null" => Some(Value::Null),
+                _ if looks_like_number => s
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|n| n.is_finite())
+                    .map(Value::Number),
+                _ => None,
+            };
+            if let Some(value) = coerced {
+                *this = value;
+            }
+        }
+        this.iter_mut().for_each(|v| v.coerce_scalars());
+    }
+
+    /// 数値の差が epsilon 以内なら等しいとみなして比較する。
+    /// 数値以外は == と同様に比較し、Array/Object は再帰的に比較する
+    pub fn approx_eq(&self, other: &Value, epsilon: f64) -> bool {
+        match (self.resolved(), other.resolved()) {
+            (Value::Number(a), Value::Number(b)) => a == b || (a - b).abs() <= epsilon,
+            (Value::Array(a), Value::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.approx_eq(b, epsilon))
+            }
+            (Value::Object(a), Value::Object(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(key, a)| b.get(key).is_some_and(|b| a.approx_eq(b, epsilon)))
+            }
+            _ => self == other,
+        }
+    }
+
+    /// keys に含まれるトップレベルのキーだけを持つ Object を返す。
+    /// self が Object でない場合は空の Object を返す
+    pub fn select(&self, keys: &[&str]) -> Value {
+        let mut object = BTreeMap::new();
+        if let Value::Object(map) = self.resolved() {
+            for key in keys {
+                if let Some(value) = map.get(*key) {
+                    object.insert(key.to_string(), value.clone());
+                }
+            }
+        }
+        Value::Object(object)
+    }
+
+    /// keys に含まれるトップレベルのキーを取り除いた Value を返す。
+    /// self が Object でない場合はそのまま複製して返す
+    pub fn omit(&self, keys: &[&str]) -> Value {
+        match self.resolved() {
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .filter(|(key, _)| !keys.contains(&key.as_str()))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+            ),
+            _ => self.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArrayMergeStrategy, NonFinitePolicy, SanitizeOptions, SqlDialect};
+    use crate::{parse, JsonError, Value};
+    use std::cmp::Ordering;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_iter_mut() {
+        let mut array = Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]);
+        array.iter_mut().for_each(|v| {
+            if let Value::Number(n) = v {
+                *n *= 10.0;
+            }
+        });
+        assert_eq!(
+            array,
+            Value::Array(vec![Value::Number(10.0), Value::Number(20.0)])
+        );
+
+        let mut object = BTreeMap::new();
+        object.insert("a".to_string(), Value::Bool(true));
+        object.insert("b".to_string(), Value::Number(1.0));
+        let mut object = Value::Object(object);
+        object.iter_mut().for_each(|v| *v = Value::Null);
+        let mut expected = BTreeMap::new();
+        expected.insert("a".to_string(), Value::Null);
+        expected.insert("b".to_string(), Value::Null);
+        assert_eq!(object, Value::Object(expected));
+
+        let mut null = Value::Null;
+        assert_eq!(null.iter_mut().count(), 0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut value = parse(r#"{"a": 1, "b": {"c": true}}"#).unwrap();
+        value.merge(parse(r#"{"b": {"d": null}, "e": "x"}"#).unwrap());
+        assert_eq!(
+            value,
+            parse(r#"{"a": 1, "b": {"c": true, "d": null}, "e": "x"}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge_with_strategy() {
+        let base = parse(r#"{"a": [1, 2]}"#).unwrap();
+        let other = parse(r#"{"a": [2, 3]}"#).unwrap();
+
+        let cases = [
+            (ArrayMergeStrategy::Replace, r#"{"a": [2, 3]}"#),
+            (ArrayMergeStrategy::Concat, r#"{"a": [1, 2, 2, 3]}"#),
+            (ArrayMergeStrategy::Union, r#"{"a": [1, 2, 3]}"#),
+        ];
+        cases.into_iter().for_each(|(strategy, expected)| {
+            let mut value = base.clone();
+            value.merge_with_strategy(other.clone(), strategy);
+            assert_eq!(value, parse(expected).unwrap(), "{:?}", strategy);
+        });
+    }
+
+    #[test]
+    fn test_select() {
+        let value = parse(r#"{"a": 1, "b": [true], "c": null}"#).unwrap();
+        assert_eq!(
+            value.select(&["a", "b"]),
+            parse(r#"{"a": 1, "b": [true]}"#).unwrap()
+        );
+        assert_eq!(
+            value.select(&["c", "missing"]),
+            parse(r#"{"c": null}"#).unwrap()
+        );
+        assert_eq!(Value::Null.select(&["a"]), Value::Object(BTreeMap::new()));
+    }
+
+    #[test]
+    fn test_omit() {
+        let value = parse(r#"{"a": 1, "b": {"c": [true]}, "d": null}"#).unwrap();
+        assert_eq!(
+            value.omit(&["a", "d", "missing"]),
+            parse(r#"{"b": {"c": [true]}}"#).unwrap()
+        );
+        assert_eq!(value.omit(&[]), value);
+        assert_eq!(Value::Bool(true).omit(&["a"]), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_try_get() {
+        let value = parse(r#"{"name": "togatoga", "age": 20}"#).unwrap();
+        assert_eq!(value.try_get::<f64>("age"), Some(20.0));
+        assert_eq!(
+            value.try_get::<String>("name"),
+            Some("togatoga".to_string())
+        );
+        assert_eq!(value.try_get::<f64>("name"), None);
+        assert_eq!(value.try_get::<bool>("missing"), None);
+        assert_eq!(Value::Null.try_get::<f64>("age"), None);
+    }
+
+    #[test]
+    fn test_get_typed_or() {
+        let value = parse(r#"{"debug": true, "name": "app", "port": 8080}"#).unwrap();
+        assert!(value.get_bool_or("debug", false));
+        assert_eq!(value.get_str_or("name", "default"), "app");
+        assert_eq!(value.get_f64_or("port", 80.0), 8080.0);
+
+        // 型が異なる
+        assert!(!value.get_bool_or("name", false));
+        assert_eq!(value.get_str_or("port", "default"), "default");
+        assert_eq!(value.get_f64_or("debug", 80.0), 80.0);
+
+        // 存在しない
+        assert!(value.get_bool_or("missing", true));
+        assert_eq!(value.get_str_or("missing", "default"), "default");
+        assert_eq!(value.get_f64_or("missing", 80.0), 80.0);
+        assert_eq!(Value::Null.get_f64_or("port", 1.0), 1.0);
+    }
+
+    #[test]
+    fn test_get_len() {
+        let value = parse(r#"{"a": [1, 2, 3], "b": {"c": {}, "d": []}, "e": "xyz"}"#).unwrap();
+        assert_eq!(value.get_object_len(), Some(3));
+        assert_eq!(value.get_array_len(), None);
+        assert_eq!(value.get("a").unwrap().get_array_len(), Some(3));
+        assert_eq!(value.get("b").unwrap().get_object_len(), Some(2));
+        assert_eq!(value.pointer("/b/c").unwrap().get_object_len(), Some(0));
+        assert_eq!(value.pointer("/b/d").unwrap().get_array_len(), Some(0));
+
+        // 文字列の長さは返さない
+        assert_eq!(value.get("e").unwrap().get_array_len(), None);
+        assert_eq!(value.get("e").unwrap().get_object_len(), None);
+        assert_eq!(Value::Null.get_array_len(), None);
+        assert_eq!(Value::Number(1.0).get_object_len(), None);
+    }
+
+    #[test]
+    fn test_first_and_last() {
+        let value = parse(r#"[1, "x", null]"#).unwrap();
+        assert_eq!(value.first(), Some(&Value::Number(1.0)));
+        assert_eq!(value.last(), Some(&Value::Null));
+
+        let value = parse("[]").unwrap();
+        assert_eq!(value.first(), None);
+        assert_eq!(value.last(), None);
+
+        let value = parse(r#"{"a": 1}"#).unwrap();
+        assert_eq!(value.first(), None);
+        assert_eq!(value.last(), None);
+    }
+
+    #[test]
+    fn test_prepend_and_insert_at() {
+        let mut value = parse("[2, 4]").unwrap();
+        value.prepend(Value::Number(1.0));
+        value.insert_at(2, Value::Number(3.0)).unwrap();
+        value.insert_at(4, Value::Number(5.0)).unwrap();
+        assert_eq!(value, parse("[1, 2, 3, 4, 5]").unwrap());
+
+        assert!(matches!(
+            value.insert_at(6, Value::Null),
+            Err(JsonError::Value(_))
+        ));
+        assert_eq!(value, parse("[1, 2, 3, 4, 5]").unwrap());
+
+        let mut value = parse(r#"{"a": 1}"#).unwrap();
+        assert!(value.insert_at(0, Value::Null).is_err());
+        value.prepend(Value::Null);
+        assert_eq!(value, parse(r#"{"a": 1}"#).unwrap());
+    }
+
+    #[test]
+    fn test_as_str_and_into_string() {
+        let value = Value::String("togatoga".to_string());
+        assert_eq!(value.as_str(), Some("togatoga"));
+
+        // 所有権ごと取り出すので、同じバッファを指したままになる
+        let ptr = value.as_str().unwrap().as_ptr();
+        let s = value.into_string().unwrap();
+        assert_eq!(s, "togatoga");
+        assert_eq!(s.as_ptr(), ptr);
+
+        assert_eq!(Value::Number(1.0).as_str(), None);
+        assert_eq!(Value::Null.into_string(), None);
+        assert_eq!(parse(r#"["a"]"#).unwrap().into_string(), None);
+    }
+
+    #[test]
+    fn test_as_str_array() {
+        let value = parse(r#"["a", "b"]"#).unwrap();
+        assert_eq!(value.as_str_array(), Some(vec!["a", "b"]));
+        assert_eq!(parse("[]").unwrap().as_str_array(), Some(vec![]));
+        assert_eq!(parse(r#"["a", 1]"#).unwrap().as_str_array(), None);
+        assert_eq!(Value::String("a".to_string()).as_str_array(), None);
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_as_base64_bytes() {
+        let value = Value::String("aGVsbG8=".to_string());
+        assert_eq!(value.as_base64_bytes(), Some(b"hello".to_vec()));
+
+        let value = Value::String("not base64!".to_string());
+        assert_eq!(value.as_base64_bytes(), None);
+        assert_eq!(Value::Number(1.0).as_base64_bytes(), None);
+    }
+
+    #[test]
+    fn test_count_matching() {
+        let value = parse(r#"{"a": null, "b": [1, null, {"c": 2, "d": null}], "e": 3}"#).unwrap();
+        assert_eq!(value.count_matching(|v| *v == Value::Null), 3);
+        assert_eq!(value.count_matching(|v| matches!(v, Value::Number(_))), 3);
+        assert_eq!(value.count_matching(|_| true), 9);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let tree = Value::Object(BTreeMap::from([
+            ("a".to_string(), Value::Number(f64::NAN)),
+            (
+                "b".to_string(),
+                Value::Array(vec![
+                    Value::Number(1.0),
+                    Value::Number(f64::INFINITY),
+                    Value::String("ok".to_string()),
+                    Value::String("bad\u{FFFD}".to_string()),
+                ]),
+            ),
+            ("\u{FFFD}".to_string(), Value::Null),
+        ]));
+
+        let mut value = tree.clone();
+        value.sanitize();
+        assert_eq!(value["a"], Value::Null);
+        assert_eq!(
+            value["b"],
+            Value::Array(vec![
+                Value::Number(1.0),
+                Value::Null,
+                Value::String("ok".to_string()),
+                Value::String("bad\u{FFFD}".to_string()),
+            ])
+        );
+        assert_eq!(value["\u{FFFD}"], Value::Null);
+
+        let mut value = tree.clone();
+        value.sanitize_with_options(&SanitizeOptions {
+            non_finite: NonFinitePolicy::Remove,
+            remove_replacement_chars: true,
+        });
+        assert_eq!(value, parse(r#"{"b": [1, "ok"]}"#).unwrap());
+
+        let mut value = Value::Number(f64::NEG_INFINITY);
+        value.sanitize_with_options(&SanitizeOptions {
+            non_finite: NonFinitePolicy::Remove,
+            ..SanitizeOptions::default()
+        });
+        assert_eq!(value, Value::Null);
+    }
+
+    #[test]
+    fn test_node_count_and_key_count() {
+        let value = parse(r#"{"a": null, "b": [1, null, {"c": 2, "d": null}], "e": 3}"#).unwrap();
+        assert_eq!(value.node_count(), 9);
+        assert_eq!(value.key_count(), 5);
+        assert_eq!(Value::Null.node_count(), 1);
+        assert_eq!(Value::Null.key_count(), 0);
+        assert_eq!(parse("[[], {}]").unwrap().key_count(), 0);
+    }
+
+    #[test]
+    fn test_replace_all() {
+        let mut value = parse(r#"{"a": "", "b": ["", "x", {"c": ""}], "d": {"e": [""]}}"#).unwrap();
+        value.replace_all(&Value::String("".to_string()), &Value::Null);
+        assert_eq!(
+            value,
+            parse(r#"{"a": null, "b": [null, "x", {"c": null}], "d": {"e": [null]}}"#).unwrap()
+        );
+
+        // 置き換え後のノードは再びたどらない
+        let mut value = parse("[1, [1]]").unwrap();
+        value.replace_all(&Value::Number(1.0), &parse("[1]").unwrap());
+        assert_eq!(value, parse("[[1], [[1]]]").unwrap());
+    }
+
+    #[test]
+    fn test_is_scalar_and_is_container() {
+        for json in [r#""a""#, "1", "true", "null"] {
+            let value = parse(json).unwrap();
+            assert!(value.is_scalar(), "{}", json);
+            assert!(!value.is_container(), "{}", json);
+        }
+        for json in ["[]", r#"{"a": 1}"#] {
+            let value = parse(json).unwrap();
+            assert!(!value.is_scalar(), "{}", json);
+            assert!(value.is_container(), "{}", json);
+        }
+        assert!(Value::raw(" [1]").unwrap().is_container());
+        assert!(Value::raw("1").unwrap().is_scalar());
+    }
+
+    #[test]
+    fn test_merge_arrays_by_key() {
+        let mut value = parse(
+            r#"[{"id": 1, "name": "a", "tags": ["x"]}, {"id": 2, "name": "b"}, {"name": "no id"}]"#,
+        )
+        .unwrap();
+        let other = parse(
+            r#"[{"id": 2, "name": "B", "age": 20}, {"id": 3, "name": "c"}, {"name": "no id"}, 1]"#,
+        )
+        .unwrap();
+        value.merge_arrays_by_key(&other, "id");
+        assert_eq!(
+            value,
+            parse(
+                r#"[
+                    {"id": 1, "name": "a", "tags": ["x"]},
+                    {"id": 2, "name": "B", "age": 20},
+                    {"name": "no id"},
+                    {"id": 3, "name": "c"},
+                    {"name": "no id"},
+                    1
+                ]"#
+            )
+            .unwrap()
+        );
+
+        let mut value = parse(r#"{"id": 1}"#).unwrap();
+        value.merge_arrays_by_key(&other, "id");
+        assert_eq!(value, parse(r#"{"id": 1}"#).unwrap());
+    }
+
+    #[test]
+    fn test_total_cmp() {
+        let values: Vec<Value> = [
+            "null",
+            "false",
+            "true",
+            "-1",
+            "2",
+            r#""""#,
+            r#""a""#,
+            "[]",
+            "[1]",
+            "[1, 2]",
+            "[2]",
+            "{}",
+            r#"{"a": 1}"#,
+            r#"{"a": 2}"#,
+            r#"{"b": 0}"#,
+        ]
+        .iter()
+        .map(|json| parse(json).unwrap())
+        .collect();
+        for (i, a) in values.iter().enumerate() {
+            for (j, b) in values.iter().enumerate() {
+                assert_eq!(a.total_cmp(b), i.cmp(&j), "{:?} {:?}", a, b);
+            }
+        }
+        assert_eq!(
+            Value::raw("[1]").unwrap().total_cmp(&parse("[1]").unwrap()),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut a =
+            parse(r#"{"b": [3, 1, {"y": [true, null], "x": 1}], "a": ["z", "y"]}"#).unwrap();
+        let mut b =
+            parse(r#"{"a": ["y", "z"], "b": [{"x": 1, "y": [null, true]}, 1, 3]}"#).unwrap();
+        assert_ne!(a, b);
+
+        a.canonicalize(true);
+        b.canonicalize(true);
+        assert_eq!(a, b);
+        assert_eq!(a.to_json_string(), b.to_json_string());
+        assert_eq!(
+            a.to_json_string(),
+            r#"{"a":["y","z"],"b":[1,3,{"x":1,"y":[null,true]}]}"#
+        );
+
+        // Raw は Parse した Value に置き換えて揃える
+        let mut raw = Value::Array(vec![Value::raw("[2, 1]").unwrap()]);
+        let mut value = parse("[[1, 2]]").unwrap();
+        assert_eq!(raw, parse("[[2, 1]]").unwrap());
+        raw.canonicalize(true);
+        value.canonicalize(true);
+        assert_eq!(raw.to_json_string(), value.to_json_string());
+
+        // sort_arrays でない場合は Array の順序を保つ
+        let mut value = parse("[3, 1, 2]").unwrap();
+        value.canonicalize(false);
+        assert_eq!(value, parse("[3, 1, 2]").unwrap());
+    }
+
+    #[test]
+    fn test_raw_reads_as_parsed() {
+        let json = r#"{"a": [1, {"b": "x"}], "c": "y", "d": 1.5}"#;
+        let raw = Value::raw(json).unwrap();
+        let value = parse(json).unwrap();
+
+        assert_eq!(raw.depth(), value.depth());
+        assert_eq!(raw.get("c"), value.get("c"));
+        assert_eq!(raw.get_ci("C"), value.get_ci("C"));
+        assert_eq!(raw.pointer("/a/1/b"), value.pointer("/a/1/b"));
+        assert_eq!(raw.pointer_all("/a/*"), value.pointer_all("/a/*"));
+        assert_eq!(raw.get_object_len(), value.get_object_len());
+        assert_eq!(raw.key_count(), value.key_count());
+        assert_eq!(raw.object_sorted(), value.object_sorted());
+        assert_eq!(raw.select(&["c"]), value.select(&["c"]));
+        assert_eq!(raw.omit(&["a"]), value.omit(&["a"]));
+        assert!(raw.iter().eq(value.iter()));
+        assert!(raw.approx_eq(&value, 0.0));
+        assert!(value.approx_eq(&raw, 0.0));
+        assert_eq!(raw.type_name(), value.type_name());
+
+        let raw = Value::raw(r#""x""#).unwrap();
+        assert_eq!(raw.as_str(), Some("x"));
+        assert_eq!(raw.clone().into_string(), Some("x".to_string()));
+        assert_eq!(String::try_from(raw), Ok("x".to_string()));
+        let raw = Value::raw("[1, 2]").unwrap();
+        assert_eq!(raw.get_array_len(), Some(2));
+        assert_eq!(raw.first(), Some(&Value::Number(1.0)));
+        assert_eq!(raw.as_vec_of::<f64>(), Some(vec![1.0, 2.0]));
+        assert_eq!(f64::try_from(Value::raw("2").unwrap()), Ok(2.0));
+        let object = Value::Object(BTreeMap::from([
+            ("n".to_string(), Value::raw("2").unwrap()),
+            ("b".to_string(), Value::raw("true").unwrap()),
+        ]));
+        assert_eq!(object.get_f64_or("n", 0.0), 2.0);
+        assert!(object.get_bool_or("b", false));
+
+        // 型の検査も Parse した Value で行う
+        let mut object = Value::raw(r#"{"a": 1}"#).unwrap();
+        assert!(object.try_merge(parse(r#"{"b": 2}"#).unwrap()).is_ok());
+        assert_eq!(object, parse(r#"{"a": 1, "b": 2}"#).unwrap());
+        assert!(object
+            .try_merge(Value::raw(r#"{"a": "x"}"#).unwrap())
+            .is_err());
+
+        // 変更する操作は Parse した Value に置き換えてから変更する
+        let mut raw = Value::raw("[1, [2]]").unwrap();
+        raw.iter_mut().for_each(|v| *v = Value::Null);
+        assert!(matches!(raw, Value::Array(_)));
+        assert_eq!(raw, parse("[null, null]").unwrap());
+        let mut raw = Value::raw(r#"{"a": [1]}"#).unwrap();
+        *raw.pointer_mut("/a/0").unwrap() = Value::Bool(true);
+        assert_eq!(raw.to_json_string(), r#"{"a":[true]}"#);
+    }
+
+    #[test]
+    fn test_try_fold() {
+        let value = parse(r#"{"a": 1, "b": [2, {"c": 3.5}, "x"], "d": null}"#).unwrap();
+        let sum = value.try_fold(0.0, |acc, v| match v {
+            Value::Number(n) => Ok::<_, ()>(acc + n),
+            _ => Ok(acc),
+        });
+        assert_eq!(sum, Ok(6.5));
+
+        // 親が先に訪問される
+        let mut order = vec![];
+        value
+            .try_fold((), |_, v| {
+                order.push(v.type_name());
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(
+            order,
+            vec!["object", "number", "array", "number", "object", "number", "string", "null"]
+        );
+
+        // 文字列が見つかった時点で中断する
+        let mut visited = 0;
+        let result = value.try_fold(0.0, |acc, v| {
+            visited += 1;
+            match v {
+                Value::Number(n) => Ok(acc + n),
+                Value::String(s) => Err(format!("error: unexpected string {}", s)),
+                _ => Ok(acc),
+            }
+        });
+        assert_eq!(result, Err("error: unexpected string x".to_string()));
+        assert_eq!(visited, 7);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_as_datetime() {
+        use chrono::{TimeZone, Utc};
+
+        let value = Value::String("2022-03-04T05:06:07+09:00".to_string());
+        assert_eq!(
+            value.as_datetime(),
+            Some(Utc.with_ymd_and_hms(2022, 3, 3, 20, 6, 7).unwrap())
+        );
+
+        let value = Value::String("2022/03/04".to_string());
+        assert_eq!(value.as_datetime(), None);
+        assert_eq!(Value::Null.as_datetime(), None);
+    }
+
+    #[test]
+    fn test_deep_contains() {
+        let value = parse(r#"{"a": [1, {"b": "x", "c": [true]}], "d": null}"#).unwrap();
+        assert!(value.deep_contains(&Value::String("x".to_string())));
+        assert!(value.deep_contains(&parse(r#"{"b": "x", "c": [true]}"#).unwrap()));
+        assert!(value.deep_contains(&value));
+        assert!(!value.deep_contains(&Value::Number(2.0)));
+        assert!(!value.deep_contains(&parse(r#"{"b": "x"}"#).unwrap()));
+    }
+
+    #[test]
+    fn test_escape_for_sql() {
+        let value = Value::String("it's a \"test\"".to_string());
+        assert_eq!(
+            value.escape_for_sql(SqlDialect::Standard).unwrap(),
+            "it''s a \"test\""
+        );
+
+        // 標準 SQL では \ はそのままで良い
+        let value = Value::String("a'b\\c".to_string());
+        assert_eq!(
+            value.escape_for_sql(SqlDialect::Standard).unwrap(),
+            "a''b\\c"
+        );
+        // MySQL は \ をエスケープの開始として解釈するので、\ と、\ を使って表す制御文字を置き換える
+        let value = Value::String("a\tb\nc\\d\u{1a}e\0f\r".to_string());
+        assert_eq!(
+            value.escape_for_sql(SqlDialect::MySql).unwrap(),
+            "a\\tb\\nc\\\\d\\Ze\\0f\\r"
+        );
+
+        // 表せない制御文字はそのまま出力せずにエラーにする
+        for dialect in [SqlDialect::Standard, SqlDialect::MySql] {
+            for s in ["\u{1b}[31m", "a\u{7f}", "\u{1}"] {
+                let value = Value::String(s.to_string());
+                assert!(
+                    value.escape_for_sql(dialect).is_err(),
+                    "{:?} {:?}",
+                    dialect,
+                    s
+                );
+            }
+        }
+        for s in ["a\0b", "a\rb", "a\tb"] {
+            let value = Value::String(s.to_string());
+            assert!(
+                value.escape_for_sql(SqlDialect::Standard).is_err(),
+                "{:?}",
+                s
+            );
+        }
+        assert_eq!(
+            Value::String("\u{1b}".to_string())
+                .escape_for_sql(SqlDialect::Standard)
+                .unwrap_err()
+                .to_string(),
+            "error: U+001B cannot be escaped in a Standard SQL string literal"
+        );
+        assert!(Value::Null.escape_for_sql(SqlDialect::Standard).is_err());
+    }
+
+    #[test]
+    fn test_map_keys() {
+        let mut value = parse(r#"{"a": {"b": [{"c": 1}]}, "d": "e"}"#).unwrap();
+        value.map_keys(|key| key.to_uppercase());
+        assert_eq!(
+            value,
+            parse(r#"{"A": {"B": [{"C": 1}]}, "D": "e"}"#).unwrap()
+        );
+
+        // 衝突した場合は後ろのキーの値が残る
+        let mut value = parse(r#"{"A": 1, "a": 2}"#).unwrap();
+        value.map_keys(|key| key.to_lowercase());
+        assert_eq!(value, parse(r#"{"a": 2}"#).unwrap());
+    }
+
+    #[test]
+    fn test_get_ci() {
+        let value = parse(r#"{"Content-Type": "text/html", "Accept": "*/*"}"#).unwrap();
+        assert_eq!(
+            value.get_ci("content-type"),
+            Some(&Value::String("text/html".to_string()))
+        );
+        assert_eq!(value.get("content-type"), None);
+        assert_eq!(value.get_ci("content-length"), None);
+        assert_eq!(Value::Null.get_ci("accept"), None);
+
+        let value = parse(r#"{"content-type": "b", "Content-Type": "a"}"#).unwrap();
+        assert_eq!(
+            value.get_ci("CONTENT-TYPE"),
+            Some(&Value::String("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_get_or() {
+        let value = parse(r#"{"name": "togatoga"}"#).unwrap();
+        let default = Value::String("unknown".to_string());
+        assert_eq!(
+            value.get_or("name", &default),
+            &Value::String("togatoga".to_string())
+        );
+        assert_eq!(value.get_or("missing", &default), &default);
+        assert_eq!(Value::Null.get_or("name", &default), &default);
+    }
+
+    #[test]
+    fn test_hash_stable_u64() {
+        let a = parse(r#"{"a": [1, "x", null], "b": {"c": true}}"#).unwrap();
+        let b = parse(r#"{"b": {"c": true}, "a": [1, "x", null]}"#).unwrap();
+        assert_eq!(a.hash_stable_u64(), b.hash_stable_u64());
+        // 実行をまたいでも変わらない値
+        assert_eq!(Value::Null.hash_stable_u64(), 0xaf63_b94c_8601_b113);
+
+        let c = parse(r#"{"a": [1, "x", null], "b": {"c": false}}"#).unwrap();
+        assert_ne!(a.hash_stable_u64(), c.hash_stable_u64());
+        assert_ne!(
+            parse(r#"["ab", "c"]"#).unwrap().hash_stable_u64(),
+            parse(r#"["a", "bc"]"#).unwrap().hash_stable_u64()
+        );
+        assert_eq!(
+            Value::Number(0.0).hash_stable_u64(),
+            Value::Number(-0.0).hash_stable_u64()
+        );
+    }
+
+    #[test]
+    fn test_try_merge() {
+        let mut value = parse(r#"{"a": 1, "b": {"c": "x"}, "d": [1]}"#).unwrap();
+        value
+            .try_merge(parse(r#"{"a": 2, "b": {"e": null}, "d": [2, 3]}"#).unwrap())
+            .unwrap();
+        assert_eq!(
+            value,
+            parse(r#"{"a": 2, "b": {"c": "x", "e": null}, "d": [2, 3]}"#).unwrap()
+        );
+
+        let mut value = parse(r#"{"a": {"b": 1}, "c": true}"#).unwrap();
+        let original = value.clone();
+        let err = value
+            .try_merge(parse(r#"{"a": {"b": "1"}, "c": false}"#).unwrap())
+            .unwrap_err();
+        match err {
+            JsonError::Value(e) => {
+                assert_eq!(e.msg, "error: cannot merge string into number at /a/b")
+            }
+            e => panic!("a value error is expected {:?}", e),
+        }
+        // エラーの場合は何も変更しない
+        assert_eq!(value, original);
+    }
+
+    #[test]
+    fn test_depth() {
+        assert_eq!(Value::Null.depth(), 1);
+        assert_eq!(parse("[]").unwrap().depth(), 1);
+        assert_eq!(parse(r#"[1, 2, "3"]"#).unwrap().depth(), 2);
+        assert_eq!(
+            parse(r#"{"a": 1, "b": {"c": {"d": true}}}"#)
+                .unwrap()
+                .depth(),
+            4
+        );
+        assert_eq!(parse("[[[[[[[[[[1]]]]]]]]]]").unwrap().depth(), 11);
+    }
+
+    #[test]
+    fn test_object_sorted() {
+        let value = parse(r#"{"b": 2, "c": 3, "a": 1}"#).unwrap();
+        let keys = value
+            .object_sorted()
+            .into_iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![
+                ("a", Value::Number(1.0)),
+                ("b", Value::Number(2.0)),
+                ("c", Value::Number(3.0)),
+            ]
+        );
+        assert!(Value::Array(vec![]).object_sorted().is_empty());
+    }
+
+    #[test]
+    fn test_coerce_scalars() {
+        let mut value = parse(r#"{"n": "3.5", "b": "true", "x": "hello"}"#).unwrap();
+        value.coerce_scalars();
+        assert_eq!(
+            value,
+            parse(r#"{"n": 3.5, "b": true, "x": "hello"}"#).unwrap()
+        );
+
+        let mut value =
+            parse(r#"[["-1e3", "null", "False", "007", "0.5", "1.2.3", "", "inf"]]"#).unwrap();
+        value.coerce_scalars();
+        assert_eq!(
+            value,
+            parse(r#"[[-1e3, null, "False", "007", 0.5, "1.2.3", "", "inf"]]"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        assert!(Value::Number(1.0000001).approx_eq(&Value::Number(1.0), 1e-6));
+        assert!(!Value::Number(1.0000001).approx_eq(&Value::Number(1.0), 1e-8));
+
+        let a = parse(r#"{"a": [0.1, 0.2], "b": "x"}"#).unwrap();
+        let b = parse(r#"{"a": [0.1000001, 0.2], "b": "x"}"#).unwrap();
+        assert!(a.approx_eq(&b, 1e-6));
+        assert!(!a.approx_eq(&b, 0.0));
+
+        let c = parse(r#"{"a": [0.1, 0.2], "b": "y"}"#).unwrap();
+        assert!(!a.approx_eq(&c, 1.0));
+        let d = parse(r#"{"a": [0.1], "b": "x"}"#).unwrap();
+        assert!(!a.approx_eq(&d, 1.0));
+    }
+}
@@ -0,0 +1,53 @@
+//! Parse 中のメモリ確保を数える。
+//! global_allocator はテストのバイナリ全体に効くので、他のテストと分けて専用のバイナリにしている。
+//! 並行して動く他のテストの確保を数えないように、テストはこのファイルの 1 つだけにする
+
+use json_parser::{Lexer, Parser};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// このスレッドでの再確保 (realloc) の回数を数えるアロケータ
+struct CountingAlloc;
+
+thread_local! {
+    static REALLOCS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = REALLOCS.try_with(|r| r.set(r.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Token 列の Parse の間に行われた再確保の回数を返す
+fn count_reallocs(json: &str) -> usize {
+    let mut parser = Parser::new(Lexer::new(json).tokenize().unwrap());
+    REALLOCS.with(|r| r.set(0));
+    parser.parse().unwrap();
+    REALLOCS.with(|r| r.get())
+}
+
+#[test]
+fn test_array_does_not_grow() {
+    // 要素数分を一度に確保するので、伸長による再確保はない
+    let numbers = (0..500).map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(count_reallocs(&format!("[{}]", numbers.join(", "))), 0);
+    assert_eq!(
+        count_reallocs(&format!("[{}]", vec!["[1, 2]"; 50].join(", "))),
+        0
+    );
+
+    // 先読みの上限を超える Array は伸長する
+    let numbers = (0..5000).map(|i| i.to_string()).collect::<Vec<_>>();
+    assert!(count_reallocs(&format!("[{}]", numbers.join(", "))) > 0);
+}